@@ -0,0 +1,300 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Broad cause of a failure, so the summary can tell users what to fix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FailureKind {
+    /// Not allowed to read or write; usually fixed by changing permissions.
+    PermissionDenied,
+    /// The entry disappeared while the run was going.
+    NotFound,
+    /// No category matched the file (`--others error`).
+    Unrecognized,
+    Other,
+}
+
+impl FailureKind {
+    /// Classifies an I/O error.
+    pub fn of(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => FailureKind::PermissionDenied,
+            io::ErrorKind::NotFound => FailureKind::NotFound,
+            _ => FailureKind::Other,
+        }
+    }
+
+    /// Lowercase name used in reports, e.g. `"permission_denied"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureKind::PermissionDenied => "permission_denied",
+            FailureKind::NotFound => "not_found",
+            FailureKind::Unrecognized => "unrecognized",
+            FailureKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::PermissionDenied => write!(f, "permission denied"),
+            FailureKind::NotFound => write!(f, "not found"),
+            FailureKind::Unrecognized => write!(f, "unrecognized"),
+            FailureKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// What went wrong with an entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl Failure {
+    /// A failure caused by `error`, described as `"{context}: {error}"`.
+    pub fn io(context: &str, error: &io::Error) -> Self {
+        Failure {
+            kind: FailureKind::of(error),
+            message: format!("{}: {}", context, error),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Why an entry was deliberately left alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Symlinks are only followed with `--follow-symlinks`.
+    Symlink,
+    /// The user answered "no" at an interactive prompt.
+    Declined,
+    /// The user quit at an interactive prompt.
+    Stopped,
+    /// The file name matched this `--exclude` pattern.
+    Excluded(String),
+    /// The name matched this pattern from `.organizerignore`.
+    Ignored(String),
+    /// `--include` patterns were given and none matched the file name.
+    NotIncluded,
+    /// An empty (zero-byte) file with `--skip-empty`.
+    Empty,
+    /// Smaller than `--min-size`.
+    TooSmall,
+    /// Larger than `--max-size`.
+    TooLarge,
+    /// Last modified before `--since`.
+    TooOld,
+    /// Last modified after `--until`.
+    TooNew,
+    /// Not modified since the last run (`--since-last-run`).
+    Unchanged,
+    /// Hidden file or folder with `--skip-hidden`.
+    Hidden,
+    /// A file of that name was already at the destination (`--on-conflict skip`).
+    Exists,
+    /// The file at the destination already has the same contents (`--on-conflict overwrite`).
+    Identical,
+    /// The file at the destination was modified at the same time or later (`--on-conflict newest`).
+    NotNewer,
+    /// No category matched and `--others leave` was given.
+    Unmatched,
+    /// A listed file (`--stdin`) that is already in one of the output folders.
+    AlreadySorted,
+    /// The file already sits in the folder it would be put into, e.g. a
+    /// photo in a folder called `Images`.
+    AlreadyOrganized,
+    /// `--limit` files were already handled by other workers.
+    LimitReached,
+    /// A subfolder, without `--recursive`.
+    NotRecursive,
+    /// A subfolder below `--max-depth`.
+    TooDeep,
+    /// The output folder or one of its category folders.
+    OutputFolder,
+    /// Moved, then put back where it was because the run failed (`--transactional`).
+    RolledBack,
+}
+
+impl SkipReason {
+    /// Whether files skipped for this reason count towards the "Skipped" bucket.
+    pub fn counts_as_skipped(&self) -> bool {
+        let uncounted = [
+            SkipReason::Symlink,
+            SkipReason::Empty,
+            SkipReason::Unchanged,
+            SkipReason::NotNewer,
+            SkipReason::Unmatched,
+            SkipReason::AlreadyOrganized,
+            SkipReason::LimitReached,
+            SkipReason::RolledBack,
+        ];
+        !uncounted.contains(self) && !self.is_folder()
+    }
+
+    /// Whether this is a folder that wasn't descended into (counted as "Dirs skipped").
+    pub fn is_folder(&self) -> bool {
+        matches!(self, SkipReason::NotRecursive | SkipReason::TooDeep | SkipReason::OutputFolder)
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Symlink => write!(f, "symlink"),
+            SkipReason::Declined => write!(f, "declined"),
+            SkipReason::Stopped => write!(f, "stopped"),
+            SkipReason::Excluded(pattern) => write!(f, "excluded by '{}'", pattern),
+            SkipReason::Ignored(pattern) => write!(f, "ignored by '{}' in .organizerignore", pattern),
+            SkipReason::NotIncluded => write!(f, "not matched by --include"),
+            SkipReason::Empty => write!(f, "empty file"),
+            SkipReason::TooSmall => write!(f, "smaller than --min-size"),
+            SkipReason::TooLarge => write!(f, "larger than --max-size"),
+            SkipReason::TooOld => write!(f, "modified before --since"),
+            SkipReason::TooNew => write!(f, "modified after --until"),
+            SkipReason::Unchanged => write!(f, "not modified since the last run"),
+            SkipReason::Hidden => write!(f, "hidden"),
+            SkipReason::Exists => write!(f, "already exists at the destination"),
+            SkipReason::Identical => write!(f, "identical file already at the destination"),
+            SkipReason::NotNewer => write!(f, "the file at the destination is as new or newer"),
+            SkipReason::Unmatched => write!(f, "no matching category"),
+            SkipReason::AlreadySorted => write!(f, "already in an output folder"),
+            SkipReason::AlreadyOrganized => write!(f, "already organized"),
+            SkipReason::LimitReached => write!(f, "--limit reached"),
+            SkipReason::NotRecursive => write!(f, "folder, not organized without --recursive"),
+            SkipReason::TooDeep => write!(f, "folder below --max-depth"),
+            SkipReason::OutputFolder => write!(f, "output folder"),
+            SkipReason::RolledBack => write!(f, "moved back by --transactional"),
+        }
+    }
+}
+
+/// What happened (or would happen) to a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionStatus {
+    Copied,
+    Moved,
+    WouldCopy,
+    WouldMove,
+    /// Not placed because a file with identical contents was already placed
+    /// into the same category this run; holds that earlier file's source path.
+    Duplicate(PathBuf),
+    /// Left alone on purpose.
+    Skipped(SkipReason),
+    /// Something went wrong.
+    Failed(Failure),
+}
+
+impl ActionStatus {
+    /// True for statuses that place a file (or would, in a dry run) into its category.
+    pub fn is_placed(&self) -> bool {
+        matches!(self, ActionStatus::Copied | ActionStatus::Moved | ActionStatus::WouldCopy | ActionStatus::WouldMove)
+    }
+}
+
+/// How a name collision at the destination was resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    /// Placed under a numbered name (`name_1.ext`) next to the existing file.
+    Renamed,
+    /// Replaced the existing file.
+    Overwritten,
+}
+
+impl Conflict {
+    /// Lowercase name used in reports, e.g. `"renamed"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Conflict::Renamed => "renamed",
+            Conflict::Overwritten => "overwritten",
+        }
+    }
+}
+
+/// How a file a dry run would place compares with what is already at its
+/// destination (`--diff`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Nothing of that name is there yet.
+    New,
+    /// A file with the same name and contents is already there.
+    Duplicate,
+    /// A different file of that name is already there.
+    Conflict,
+}
+
+impl Change {
+    /// Lowercase name used in reports, e.g. `"new"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Change::New => "new",
+            Change::Duplicate => "duplicate",
+            Change::Conflict => "conflict",
+        }
+    }
+}
+
+/// The outcome for one entry seen during a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileAction {
+    pub source: PathBuf,
+    /// Where the file ended up (or would end up). `None` when nothing was placed.
+    pub destination: Option<PathBuf>,
+    /// Category the file was classified into. `None` if it never got that far.
+    pub category: Option<String>,
+    /// Subfolder of the category the file went into with `--subcategorize`, e.g. `"Rust"`.
+    pub subcategory: Option<String>,
+    /// Type recognized from the file's contents (`--sniff-content`) and used
+    /// for the category instead of the extension, e.g. `"png"`.
+    pub content_type: Option<String>,
+    pub status: ActionStatus,
+    /// Size of the file in bytes, if it got far enough to be looked at.
+    pub size: Option<u64>,
+    /// Set if the destination name was taken and the file was renamed or overwrote it.
+    pub conflict: Option<Conflict>,
+    /// A problem that didn't stop the file from being handled (counted as an error).
+    pub warning: Option<String>,
+    /// With `diff` in a dry run, how the destination compares with what's there.
+    pub change: Option<Change>,
+}
+
+impl FileAction {
+    /// An action for an entry that failed before it could be classified.
+    pub fn failed(source: PathBuf, failure: Failure) -> Self {
+        FileAction {
+            source,
+            destination: None,
+            category: None,
+            subcategory: None,
+            content_type: None,
+            status: ActionStatus::Failed(failure),
+            size: None,
+            conflict: None,
+            warning: None,
+            change: None,
+        }
+    }
+
+    /// An action for an entry that was skipped before it was classified.
+    pub fn skipped(source: PathBuf, reason: SkipReason) -> Self {
+        FileAction {
+            source,
+            destination: None,
+            category: None,
+            subcategory: None,
+            content_type: None,
+            status: ActionStatus::Skipped(reason),
+            size: None,
+            conflict: None,
+            warning: None,
+            change: None,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! `--extract-archives`: sorts the files inside `.zip` archives instead of
+//! the archives themselves.
+//!
+//! An archive is unpacked into a staging folder first and every file in it
+//! then goes through the run like any other file. Entries whose path would
+//! land outside the archive (`../`, absolute paths) and symbolic links are
+//! never extracted. Zips inside a zip stay files unless `--extract-nested`
+//! is given.
+
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{Local, NaiveDate, TimeZone};
+use zip::ZipArchive;
+
+/// How deep `--extract-nested` goes into zips inside zips.
+const MAX_NESTING: usize = 8;
+
+/// A file taken out of an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extracted {
+    /// Where it was written.
+    pub staged: PathBuf,
+    /// Its path inside the archive, e.g. `photos/cat.jpg` (or
+    /// `inner.zip/cat.jpg` for one from a nested archive).
+    pub inner: PathBuf,
+}
+
+/// What [`extract`] found in an archive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Contents {
+    pub files: Vec<Extracted>,
+    /// Entries that weren't extracted, as named in the archive, with the reason.
+    pub refused: Vec<(String, &'static str)>,
+}
+
+/// Whether `path` is a zip archive, going by its extension.
+pub fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Unpacks the files of the zip at `archive` into `staging`, each into a
+/// numbered folder of its own so equal names from different folders of
+/// the archive don't clash. With `nested`, zips inside are unpacked too.
+pub fn extract(archive: &Path, staging: &Path, nested: bool) -> io::Result<Contents> {
+    let mut contents = Contents::default();
+    extract_from(File::open(archive)?, Path::new(""), staging, nested, 0, &mut contents)?;
+    Ok(contents)
+}
+
+fn extract_from<R: Read + Seek>(
+    reader: R,
+    prefix: &Path,
+    staging: &Path,
+    nested: bool,
+    depth: usize,
+    contents: &mut Contents,
+) -> io::Result<()> {
+    let mut zip = ZipArchive::new(reader)?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        // Joined by hand: a `/etc/...` entry shouldn't read as an absolute path
+        let shown = if prefix.as_os_str().is_empty() {
+            entry.name()?.into_owned()
+        } else {
+            format!("{}/{}", prefix.display(), entry.name()?)
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        if entry.is_symlink() {
+            contents.refused.push((shown, "symbolic links aren't extracted"));
+            continue;
+        }
+        let Some(name) = entry.enclosed_name() else {
+            contents.refused.push((shown, "its path leads outside the archive"));
+            continue;
+        };
+        let inner = prefix.join(&name);
+        if nested && depth < MAX_NESTING && is_zip(&name) {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            extract_from(Cursor::new(data), &inner, staging, nested, depth + 1, contents)?;
+            continue;
+        }
+        // enclosed_name() only returns names that end in a file name
+        let Some(file_name) = name.file_name() else { continue };
+        let dir = staging.join(contents.files.len().to_string());
+        fs::create_dir_all(&dir)?;
+        let staged = dir.join(file_name);
+        let mut file = File::options().write(true).create_new(true).open(&staged)?;
+        io::copy(&mut entry, &mut file)?;
+        if let Some(time) = entry.last_modified().and_then(zip_time) {
+            // Keeps sorting by date working; a file without it just looks new
+            let _ = file.set_modified(time);
+        }
+        contents.files.push(Extracted { staged, inner });
+    }
+    Ok(())
+}
+
+/// A zip entry's timestamp (local time, to the second) as a `SystemTime`.
+fn zip_time(time: zip::DateTime) -> Option<SystemTime> {
+    let date = NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?;
+    let time = date.and_hms_opt(time.hour().into(), time.minute().into(), time.second().into())?;
+    Some(Local.from_local_datetime(&time).earliest()?.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    fn zip_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn entries_are_unpacked_but_never_outside_the_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = zip_of(&[("deep.txt", b"deep")]);
+        let outer = zip_of(&[
+            ("a/cat.jpg", b"cat"),
+            ("b/cat.jpg", b"other cat"),
+            ("../evil.sh", b"evil"),
+            ("a/../../evil.sh", b"evil"),
+            ("inner.zip", &inner),
+        ]);
+        let archive = dir.path().join("photos.zip");
+        fs::write(&archive, outer).unwrap();
+
+        let staging = dir.path().join("staging");
+        let contents = extract(&archive, &staging, false).unwrap();
+        let inners: Vec<_> = contents.files.iter().map(|f| f.inner.clone()).collect();
+        assert_eq!(inners, [Path::new("a/cat.jpg"), Path::new("b/cat.jpg"), Path::new("inner.zip")]);
+        assert_eq!(fs::read(&contents.files[1].staged).unwrap(), b"other cat");
+        assert_eq!(contents.refused.len(), 2);
+        assert!(!dir.path().join("evil.sh").exists());
+
+        let contents = extract(&archive, &dir.path().join("nested"), true).unwrap();
+        assert_eq!(contents.files.last().unwrap().inner, Path::new("inner.zip/deep.txt"));
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Category -> subcategory -> extensions, see [`build_subcategory_map`].
+pub type SubcategoryMap = HashMap<String, HashMap<String, Vec<String>>>;
+
+/// Category hidden files are put in with [`HiddenFiles::Separate`].
+pub const HIDDEN_CATEGORY: &str = "Hidden";
+
+/// Category files below the `--small-threshold` are put in, whatever their type.
+pub const SMALL_FILES_CATEGORY: &str = "SmallFiles";
+
+/// Files smaller than this go to [`SMALL_FILES_CATEGORY`] with
+/// `--group-small-files` when no `--small-threshold` is given (10 KiB).
+pub const DEFAULT_SMALL_THRESHOLD: u64 = 10 * 1024;
+
+/// How files whose name starts with a `.` are treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HiddenFiles {
+    /// Sorted by extension like any other file.
+    #[default]
+    Sort,
+    /// Left where they are (hidden folders aren't descended into either).
+    Skip,
+    /// Put into their own "Hidden" category.
+    Separate,
+}
+
+/// What happens to files that match no category.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OthersPolicy {
+    /// Put into the "Others" folder.
+    #[default]
+    Folder,
+    /// Left where they are, counted as "Unmatched".
+    Leave,
+    /// Counted as an error, for pipelines that expect every file to be known.
+    Error,
+}
+
+impl FromStr for OthersPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "folder" => Ok(OthersPolicy::Folder),
+            "leave" => Ok(OthersPolicy::Leave),
+            "error" => Ok(OthersPolicy::Error),
+            _ => Err(format!("unknown --others mode '{}' (expected folder, leave or error)", s)),
+        }
+    }
+}
+
+/// True if the file name starts with a `.`, the Unix convention for hidden
+/// files. Only the name is looked at, so the Windows hidden attribute is not
+/// taken into account.
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.'))
+}
+
+/// Returns lowercase extension string for a path, e.g. "jpg" or "" if none.
+pub fn file_extension_lowercase(path: &Path) -> String {
+    file_extension(path, false)
+}
+
+/// Multi-part extensions that are treated as one, so `backup.tar.gz` is a
+/// `tar.gz` rather than just a `gz`.
+pub const COMPOUND_EXTENSIONS: [&str; 3] = ["tar.gz", "tar.bz2", "tar.xz"];
+
+/// Returns the extension of a path ("" if none), lowercased unless
+/// `case_sensitive` is set. Known compound extensions such as `tar.gz` are
+/// returned whole.
+pub fn file_extension(path: &Path, case_sensitive: bool) -> String {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let ext = match compound_extension_len(name) {
+        Some(len) => &name[name.len() - len..],
+        None => path.extension().and_then(|ext| ext.to_str()).unwrap_or_default(),
+    };
+    if case_sensitive { ext.to_string() } else { ext.to_lowercase() }
+}
+
+/// Length of the compound extension `name` ends with (without the leading
+/// dot), if any. The name must have something before it.
+pub fn compound_extension_len(name: &str) -> Option<usize> {
+    let lower = name.to_ascii_lowercase();
+    COMPOUND_EXTENSIONS
+        .iter()
+        .find(|ext| lower.len() > ext.len() + 1 && lower.ends_with(&format!(".{}", ext)))
+        .map(|ext| ext.len())
+}
+
+/// The built-in categories with their extensions, in the order the summary
+/// lists them.
+const DEFAULT_CATEGORIES: [(&str, &[&str]); 11] = [
+    (
+        "Images",
+        &["jpg", "jpeg", "png", "gif", "svg", "bmp", "webp", "cr2", "nef", "arw", "dng", "raf", "orf", "rw2"],
+    ),
+    ("Documents", &["pdf", "doc", "docx", "txt"]),
+    ("Spreadsheets", &["xls", "xlsx", "ods", "csv"]),
+    ("Presentations", &["ppt", "pptx", "odp", "key"]),
+    ("Ebooks", &["epub", "mobi", "azw3"]),
+    ("Videos", &["mp4", "mov", "mkv", "webm", "avi"]),
+    ("Audio", &["mp3", "wav", "flac", "aac"]),
+    ("Archives", &["zip", "rar", "tar", "gz", "7z", "tar.gz", "tar.bz2", "tar.xz"]),
+    ("Disk Images", &["iso", "dmg", "img"]),
+    ("Fonts", &["ttf", "otf", "woff", "woff2"]),
+    ("Code", &["rs", "py", "js", "ts", "go", "java", "c", "cpp", "html", "css", "json", "yaml", "yml"]),
+];
+
+/// Names of the built-in categories, in the order the summary lists them.
+pub fn builtin_categories() -> impl Iterator<Item = &'static str> {
+    DEFAULT_CATEGORIES.iter().map(|(cat, _)| *cat)
+}
+
+/// Build a mapping of category -> Vec<extensions>
+pub fn build_category_map() -> HashMap<String, Vec<String>> {
+    DEFAULT_CATEGORIES
+        .iter()
+        .map(|(cat, exts)| (cat.to_string(), exts.iter().map(|e| e.to_string()).collect()))
+        .collect()
+}
+
+/// Category for a MIME type recognized by [`crate::sniff`], by family:
+/// `image/*` go to Images, `video/*` to Videos, `audio/*` to Audio, PDFs to
+/// Documents and compressed files to Archives. `None` if the family has no
+/// category, or `categories` doesn't have the one it maps to.
+pub fn category_for_mime<'a>(mime: &str, categories: &'a HashMap<String, Vec<String>>) -> Option<&'a str> {
+    let (family, subtype) = mime.split_once('/')?;
+    let category = match (family, subtype) {
+        ("image", _) => "Images",
+        ("video", _) => "Videos",
+        ("audio", _) => "Audio",
+        ("application", "pdf") => "Documents",
+        ("application", "zip" | "gzip" | "vnd.rar" | "x-7z-compressed") => "Archives",
+        _ => return None,
+    };
+    categories.get_key_value(category).map(|(name, _)| name.as_str())
+}
+
+/// Build the mapping of category -> subcategory -> extensions used by
+/// `--subcategorize`, e.g. `.rs` files in "Code" go to `Code/Rust`.
+pub fn build_subcategory_map() -> SubcategoryMap {
+    type Subcategories = &'static [(&'static str, &'static [&'static str])];
+    let defaults: [(&str, Subcategories); 2] = [
+        ("Images", &[("Raw", &["cr2", "nef", "arw", "dng", "raf", "orf", "rw2"])]),
+        (
+            "Code",
+            &[
+                ("Rust", &["rs"]),
+                ("Python", &["py"]),
+                ("JavaScript", &["js"]),
+                ("TypeScript", &["ts"]),
+                ("Go", &["go"]),
+                ("Java", &["java"]),
+                ("C", &["c"]),
+                ("C++", &["cpp"]),
+                ("Web", &["html", "css"]),
+                ("Data", &["json", "yaml", "yml"]),
+            ],
+        ),
+    ];
+    defaults
+        .iter()
+        .map(|(cat, subs)| {
+            let subs = subs
+                .iter()
+                .map(|(sub, exts)| (sub.to_string(), exts.iter().map(|e| e.to_string()).collect()))
+                .collect();
+            (cat.to_string(), subs)
+        })
+        .collect()
+}
+
+/// Subcategory of `category` that lists `ext`, if any. Files without one go
+/// directly into the category folder.
+pub fn subcategory_for_extension<'a>(
+    category: &str,
+    ext: &str,
+    subcategories: &'a SubcategoryMap,
+    case_sensitive: bool,
+) -> Option<&'a str> {
+    let wanted = if case_sensitive { ext.to_string() } else { ext.to_lowercase() };
+    subcategories.get(category)?.iter().find_map(|(sub, exts)| {
+        let found = exts.iter().any(|e| if case_sensitive { *e == wanted } else { e.to_lowercase() == wanted });
+        found.then_some(sub.as_str())
+    })
+}
+
+/// Extension -> category, built once from a category map so classifying a
+/// file is a single lookup rather than a scan of every category's list.
+#[derive(Debug, Clone, Default)]
+pub struct ExtensionIndex<'a> {
+    /// Keys are lowercased unless `case_sensitive`.
+    categories: HashMap<String, &'a str>,
+    case_sensitive: bool,
+}
+
+impl<'a> ExtensionIndex<'a> {
+    /// Indexes `categories`. An extension listed in two categories belongs to
+    /// the one named first in `priority`, or else to the one that sorts first.
+    pub fn new(categories: &'a HashMap<String, Vec<String>>, case_sensitive: bool, priority: &[String]) -> Self {
+        let mut sorted: Vec<_> = categories.iter().collect();
+        let rank = |cat: &String| priority.iter().position(|p| p == cat).unwrap_or(priority.len());
+        sorted.sort_by_key(|&(cat, _)| (rank(cat), cat));
+        let mut index = HashMap::new();
+        for (cat, exts) in sorted {
+            for ext in exts {
+                let key = if case_sensitive { ext.clone() } else { ext.to_lowercase() };
+                index.entry(key).or_insert(cat.as_str());
+            }
+        }
+        ExtensionIndex { categories: index, case_sensitive }
+    }
+
+    /// Category `ext` belongs to, or "Others".
+    pub fn category(&self, ext: &str) -> &'a str {
+        let found = if self.case_sensitive {
+            self.categories.get(ext)
+        } else {
+            self.categories.get(&ext.to_lowercase())
+        };
+        found.copied().unwrap_or("Others")
+    }
+}
+
+/// Given an extension, find category name, or "Others"
+pub fn category_for_extension<'a>(ext: &str, categories: &'a HashMap<String, Vec<String>>) -> &'a str {
+    category_for_extension_with(ext, categories, false)
+}
+
+/// Like [`category_for_extension`]. With `case_sensitive` the extension has to
+/// match a category entry exactly, so `JPG` is not `jpg`; otherwise case is ignored.
+///
+/// This indexes `categories` for the one lookup; build an [`ExtensionIndex`]
+/// to classify many files.
+pub fn category_for_extension_with<'a>(
+    ext: &str,
+    categories: &'a HashMap<String, Vec<String>>,
+    case_sensitive: bool,
+) -> &'a str {
+    ExtensionIndex::new(categories, case_sensitive, &[]).category(ext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_builtin_extension_maps_to_its_category() {
+        let categories = build_category_map();
+        for (category, exts) in &categories {
+            for ext in exts {
+                assert_eq!(category_for_extension(ext, &categories), category, "extension {}", ext);
+            }
+        }
+        assert_eq!(category_for_extension("jpg", &categories), "Images");
+        assert_eq!(category_for_extension("pdf", &categories), "Documents");
+        assert_eq!(category_for_extension("mkv", &categories), "Videos");
+        assert_eq!(category_for_extension("flac", &categories), "Audio");
+        assert_eq!(category_for_extension("7z", &categories), "Archives");
+        assert_eq!(category_for_extension("tar.bz2", &categories), "Archives");
+        assert_eq!(category_for_extension("rs", &categories), "Code");
+        assert_eq!(category_for_extension("xlsx", &categories), "Spreadsheets");
+        assert_eq!(category_for_extension("key", &categories), "Presentations");
+        assert_eq!(category_for_extension("woff2", &categories), "Fonts");
+        assert_eq!(category_for_extension("iso", &categories), "Disk Images");
+    }
+
+    #[test]
+    fn unknown_and_empty_extensions_go_to_others() {
+        let categories = build_category_map();
+        assert_eq!(category_for_extension("xyz", &categories), "Others");
+        assert_eq!(category_for_extension("", &categories), "Others");
+    }
+
+    #[test]
+    fn case_sensitive_lookup_needs_an_exact_match() {
+        let categories = build_category_map();
+        assert_eq!(category_for_extension_with("JPG", &categories, false), "Images");
+        assert_eq!(category_for_extension_with("JPG", &categories, true), "Others");
+    }
+
+    #[test]
+    fn extension_in_two_categories_resolves_the_same_every_time() {
+        let mut categories = build_category_map();
+        categories.insert("Web".to_string(), vec!["html".to_string(), "svg".to_string()]);
+        for _ in 0..20 {
+            // A fresh map each time, so the hash order differs between tries
+            let categories: HashMap<_, _> = categories.clone().into_iter().collect();
+            assert_eq!(category_for_extension("html", &categories), "Code");
+            assert_eq!(category_for_extension("svg", &categories), "Images");
+        }
+    }
+
+    #[test]
+    fn priority_decides_between_categories() {
+        let mut categories = build_category_map();
+        categories.insert("Web".to_string(), vec!["html".to_string(), "svg".to_string()]);
+        let priority = ["Web".to_string(), "Images".to_string()];
+        let index = ExtensionIndex::new(&categories, false, &priority);
+        assert_eq!(index.category("svg"), "Web");
+        assert_eq!(index.category("html"), "Web");
+        assert_eq!(index.category("png"), "Images");
+    }
+
+    #[test]
+    fn extension_edge_cases() {
+        assert_eq!(file_extension_lowercase(Path::new("photo.JPG")), "jpg");
+        assert_eq!(file_extension_lowercase(Path::new("archive.tar.gz")), "tar.gz");
+        assert_eq!(file_extension_lowercase(Path::new("Backup.TAR.XZ")), "tar.xz");
+        assert_eq!(file_extension_lowercase(Path::new("notes.v2.txt")), "txt");
+        assert_eq!(file_extension_lowercase(Path::new(".tar.gz")), "gz");
+        assert_eq!(file_extension_lowercase(Path::new("README")), "");
+        assert_eq!(file_extension_lowercase(Path::new(".bashrc")), "");
+        assert_eq!(file_extension_lowercase(Path::new("dir.d/file")), "");
+        assert_eq!(file_extension(Path::new("photo.JPG"), true), "JPG");
+    }
+
+    #[test]
+    fn mime_families_map_to_categories() {
+        let categories = build_category_map();
+        assert_eq!(category_for_mime("image/png", &categories), Some("Images"));
+        assert_eq!(category_for_mime("audio/mp4", &categories), Some("Audio"));
+        assert_eq!(category_for_mime("application/pdf", &categories), Some("Documents"));
+        assert_eq!(category_for_mime("application/x-executable", &categories), None);
+        assert_eq!(category_for_mime("image/png", &HashMap::new()), None);
+    }
+}
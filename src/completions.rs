@@ -0,0 +1,306 @@
+//! Shell completion scripts for `--generate-completions` and the option
+//! list of `--help`, built from the table of command-line flags.
+
+/// What follows a flag on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    /// Nothing; the flag is a switch.
+    None,
+    /// A file path.
+    File,
+    /// A folder path.
+    Dir,
+    /// One of a fixed set of words.
+    Choice(&'static [&'static str]),
+    /// Anything else (a number, a pattern, ...), described by this name.
+    Other(&'static str),
+}
+
+/// One command-line flag.
+#[derive(Debug, Clone, Copy)]
+pub struct Flag {
+    pub long: &'static str,
+    pub short: Option<char>,
+    pub value: Value,
+    pub help: &'static str,
+}
+
+const fn flag(long: &'static str, short: Option<char>, value: Value, help: &'static str) -> Flag {
+    Flag { long, short, value, help }
+}
+
+/// Every flag the tool understands, except the hidden `--generate-completions`.
+pub const FLAGS: &[Flag] = &[
+    flag("--dry-run", Some('n'), Value::None, "Only show what would happen"),
+    flag("--move", Some('m'), Value::None, "Move files instead of copying them"),
+    flag("--link", None, Value::Choice(&["hard", "sym"]), "Link files into the categories instead of copying them"),
+    flag("--recursive", Some('r'), Value::None, "Organize subfolders too"),
+    flag("--max-depth", None, Value::Other("N"), "How many levels of subfolders to descend into"),
+    flag("--config", None, Value::File, "Categories from a TOML or JSON file"),
+    flag("--merge-config", None, Value::None, "Layer the config over the built-in categories"),
+    flag("--no-config", None, Value::None, "Ignore the personal config file"),
+    flag("--profile", None, Value::Other("NAME"), "Use a profile from the config"),
+    flag("--list-profiles", None, Value::None, "List the config's profiles"),
+    flag("--map", None, Value::Other("EXTS=CATEGORY"), "Send extensions to a category for this run"),
+    flag("--rules", None, Value::File, "Classification rules (name, size, date) tried first"),
+    flag("--priority", None, Value::Other("CATEGORIES"), "Categories that win when an extension is in several"),
+    flag("--diff", None, Value::None, "Dry run listing only files that are new or changed"),
+    flag("--tree", None, Value::None, "Show a dry run as a tree of the folders it would fill"),
+    flag("--emit-sh", None, Value::None, "Print a dry run as a shell script of cp/mv commands"),
+    flag("--json", None, Value::None, "Print a JSON report"),
+    flag("--summary-json", None, Value::None, "Print only the counts as JSON"),
+    flag("--output", Some('o'), Value::Dir, "Folder to create the category folders in"),
+    flag("--interactive", Some('i'), Value::None, "Ask before each file"),
+    flag("--dedup", None, Value::None, "Skip files with the same contents"),
+    flag("--by-date", None, Value::None, "Sort into date folders by modified time"),
+    flag("--by-owner", None, Value::None, "Put files in a folder per owning user first (Unix)"),
+    flag("--date-only", None, Value::None, "Use only date folders, without categories"),
+    flag("--date-format", None, Value::Other("FMT"), "strftime pattern for date folders"),
+    flag("--color", None, Value::Choice(&["auto", "always", "never"]), "When to color the output"),
+    flag("--date-field", None, Value::Choice(&["created", "modified", "accessed"]), "Which timestamp date folders use"),
+    flag("--by-exif-date", None, Value::None, "Date photos by when they were taken (EXIF)"),
+    flag("--quiet", Some('q'), Value::None, "Only print the summary"),
+    flag("--verbose", Some('v'), Value::None, "Explain how each file was classified"),
+    flag("--timing", None, Value::None, "Show how long the run took and how fast it went"),
+    flag("--log", None, Value::File, "Append every copy or move to a log file"),
+    flag("--exec", None, Value::Other("CMD"), "Run a command for each placed file"),
+    flag("--exec-shell", None, Value::Other("CMD"), "Run a shell command for each placed file"),
+    flag("--undo", None, Value::File, "Revert the operations in a log file"),
+    flag("--plan", None, Value::File, "Write the planned operations to a file"),
+    flag("--csv", None, Value::File, "Write a CSV file with one row per file"),
+    flag("--apply", None, Value::File, "Carry out a plan written by --plan"),
+    flag("--include", None, Value::Other("GLOB"), "Only organize matching file names"),
+    flag("--exclude", None, Value::Other("GLOB"), "Never organize matching file names"),
+    flag("--skip-empty", None, Value::None, "Leave empty files where they are"),
+    flag("--min-size", None, Value::Other("SIZE"), "Skip smaller files"),
+    flag("--max-size", None, Value::Other("SIZE"), "Skip larger files"),
+    flag("--since", None, Value::Other("DATE"), "Skip files modified before a date (YYYY-MM-DD or 7d)"),
+    flag("--since-last-run", None, Value::None, "Only organize files modified since the last successful run"),
+    flag("--until", None, Value::Other("DATE"), "Skip files modified after a date (YYYY-MM-DD or 7d)"),
+    flag("--jobs", Some('j'), Value::Other("N"), "Number of worker threads"),
+    flag("--no-progress", None, Value::None, "Don't show a progress bar"),
+    flag("--preserve-times", None, Value::None, "Keep the timestamps of copied files"),
+    flag(
+        "--preserve",
+        None,
+        Value::Choice(&["times", "all"]),
+        "What copies keep: timestamps, or also owner and extended attributes",
+    ),
+    flag("--verify", None, Value::None, "Check every copy against its original"),
+    flag("--case-sensitive", None, Value::None, "Match extensions exactly"),
+    flag("--sniff-content", None, Value::None, "Recognize unknown files by their contents"),
+    flag("--by-mime", None, Value::None, "Sort by the MIME type of the contents"),
+    flag(
+        "--on-conflict",
+        None,
+        Value::Choice(&["rename", "skip", "overwrite", "newest"]),
+        "What to do when a name is taken",
+    ),
+    flag("--trash-on-overwrite", None, Value::None, "Move files replaced by overwrite to the trash"),
+    flag("--others", None, Value::Choice(&["folder", "leave", "error"]), "What to do with files no category matches"),
+    flag("--conflict-format", None, Value::Other("TEMPLATE"), "How renamed files are named"),
+    flag("--rename", None, Value::Other("RULES"), "Tidy up file names as they are placed"),
+    flag("--normalize-unicode", None, Value::None, "Use composed (NFC) Unicode in destination names"),
+    flag("--extract-archives", None, Value::None, "Sort the files inside zip archives instead"),
+    flag("--extract-nested", None, Value::None, "With --extract-archives, unpack zips inside zips too"),
+    flag("--follow-symlinks", None, Value::None, "Organize what symlinks point to"),
+    flag("--skip-hidden", None, Value::None, "Leave hidden files alone"),
+    flag("--hidden-category", None, Value::None, "Put hidden files in a Hidden folder"),
+    flag("--group-small-files", None, Value::None, "Put files under 10 KiB in a SmallFiles folder"),
+    flag("--small-threshold", None, Value::Other("SIZE"), "Size below which files go to SmallFiles"),
+    flag("--subcategorize", None, Value::None, "Add a subfolder per kind inside categories"),
+    flag("--by-prefix", None, Value::None, "Group files sharing a name prefix into a subfolder"),
+    flag("--prefix-min", None, Value::Other("N"), "How many files must share a prefix to be grouped"),
+    flag("--tag-pattern", None, Value::Other("REGEX"), "Group files by a tag in their name (first capture group)"),
+    flag("--fail-fast", None, Value::None, "Stop at the first error"),
+    flag("--force", None, Value::None, "Allow a home, root or system folder, or a drive short of space"),
+    flag("--yes", Some('y'), Value::None, "Don't ask before copying or moving"),
+    flag("--transactional", None, Value::None, "With --move, undo the run's moves if anything fails"),
+    flag("--limit", None, Value::Other("N"), "Stop after organizing N files"),
+    flag("--retries", None, Value::Other("N"), "Try copies again N times on timeouts and interruptions"),
+    flag("--flatten", None, Value::None, "Collect files from all subfolders"),
+    flag("--flatten-remove-empty", None, Value::None, "Flatten and remove the emptied subfolders"),
+    flag("--prune-empty-dirs", None, Value::None, "After --move, remove the subfolders it emptied"),
+    flag("--prune-all-empty", None, Value::None, "After --move, remove every empty subfolder"),
+    flag("--rename-blocked-folders", None, Value::None, "Use Images_files if a file is named Images"),
+    flag("--keep-structure", None, Value::None, "Recreate the subfolder layout inside each category"),
+    flag("--summary-out", None, Value::File, "Also save the summary to a file"),
+    flag("--stats-only", None, Value::None, "Only report what the folder is made of"),
+    flag("--extension-report", None, Value::None, "List the extensions in the folder and their categories"),
+    flag("--report-only-others", None, Value::None, "List the files no category matches"),
+    flag("--top", None, Value::Other("N"), "How many of the largest files --stats-only lists"),
+    flag("--no-ignore", None, Value::None, "Don't read .organizerignore"),
+    flag("--stdin", None, Value::None, "Organize the files listed on stdin into --output"),
+    flag("--null", Some('0'), Value::None, "Like --stdin, with NUL-separated paths (find -print0)"),
+    flag("--help", Some('h'), Value::None, "Show this help"),
+    flag("--version", Some('V'), Value::None, "Show the version"),
+];
+
+/// True if `arg` is one of [`FLAGS`], by long or short name.
+pub fn is_known(arg: &str) -> bool {
+    FLAGS.iter().any(|f| names(f).iter().any(|name| name == arg))
+}
+
+/// One line per flag with what it does, for `--help`:
+///
+/// ```text
+///   -o, --output DIR        Folder to create the category folders in
+///       --max-depth N       How many levels of subfolders to descend into
+/// ```
+pub fn options_help() -> String {
+    let heads: Vec<String> = FLAGS
+        .iter()
+        .map(|flag| {
+            let short = flag.short.map_or("    ".to_string(), |c| format!("-{}, ", c));
+            match flag.value {
+                Value::None => format!("{}{}", short, flag.long),
+                Value::File => format!("{}{} FILE", short, flag.long),
+                Value::Dir => format!("{}{} DIR", short, flag.long),
+                Value::Choice(words) => format!("{}{} {}", short, flag.long, words.join("|")),
+                Value::Other(name) => format!("{}{} {}", short, flag.long, name),
+            }
+        })
+        .collect();
+    let width = heads.iter().map(|head| head.len()).max().unwrap_or(0);
+    let mut help = String::new();
+    for (head, flag) in heads.iter().zip(FLAGS) {
+        help.push_str(&format!("  {:<width$}  {}\n", head, flag.help));
+    }
+    help
+}
+
+/// Shells a completion script can be generated for.
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// The completion script for `shell`, for a binary called `bin`.
+pub fn generate(shell: &str, bin: &str) -> Result<String, String> {
+    match shell {
+        "bash" => Ok(bash(bin)),
+        "zsh" => Ok(zsh(bin)),
+        "fish" => Ok(fish(bin)),
+        _ => Err(format!("unknown shell '{}' (expected {})", shell, SHELLS.join(", "))),
+    }
+}
+
+/// Long and short names of a flag, e.g. `["--output", "-o"]`.
+fn names(flag: &Flag) -> Vec<String> {
+    let mut names = vec![flag.long.to_string()];
+    names.extend(flag.short.map(|c| format!("-{}", c)));
+    names
+}
+
+/// `|`-separated names of the flags taking values of the given kind.
+fn bash_case(pred: impl Fn(&Value) -> bool) -> String {
+    FLAGS.iter().filter(|f| pred(&f.value)).flat_map(names).collect::<Vec<_>>().join("|")
+}
+
+fn bash(bin: &str) -> String {
+    let func = format!("_{}", bin.replace('-', "_"));
+    let all: Vec<String> = FLAGS.iter().flat_map(names).collect();
+    let mut choices = String::new();
+    for flag in FLAGS {
+        if let Value::Choice(words) = flag.value {
+            choices.push_str(&format!(
+                "        {})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            return ;;\n",
+                names(flag).join("|"),
+                words.join(" ")
+            ));
+        }
+    }
+    format!(
+        r#"# bash completion for {bin}
+{func}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+        {files})
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return ;;
+        {dirs})
+            COMPREPLY=($(compgen -d -- "$cur"))
+            return ;;
+{choices}        {other})
+            return ;;
+    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{all}" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -d -- "$cur"))
+    fi
+}}
+complete -o filenames -F {func} {bin}
+"#,
+        files = bash_case(|v| *v == Value::File),
+        dirs = bash_case(|v| *v == Value::Dir),
+        other = bash_case(|v| matches!(v, Value::Other(_))),
+        all = all.join(" "),
+    )
+}
+
+/// Escapes the characters `_arguments` gives a meaning to in descriptions.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:").replace('\'', "'\\''")
+}
+
+fn zsh(bin: &str) -> String {
+    let mut specs = Vec::new();
+    for flag in FLAGS {
+        let action = match flag.value {
+            Value::None => String::new(),
+            Value::File => ":file:_files".to_string(),
+            Value::Dir => ":folder:_files -/".to_string(),
+            Value::Choice(words) => format!(":value:({})", words.join(" ")),
+            Value::Other(name) => format!(":{}: ", name.to_lowercase()),
+        };
+        let help = zsh_escape(flag.help);
+        let spec = match flag.short {
+            Some(c) => format!("'(-{c} {long})'{{-{c},{long}}}'[{help}]{action}'", long = flag.long),
+            None => format!("'{}[{}]{}'", flag.long, help, action),
+        };
+        specs.push(format!("    {} \\", spec));
+    }
+    format!("#compdef {bin}\n\n_arguments -s \\\n{}\n    '1:folder:_files -/'\n", specs.join("\n"))
+}
+
+fn fish(bin: &str) -> String {
+    let mut lines = vec![
+        format!("# fish completion for {}", bin),
+        format!("complete -c {} -f -a '(__fish_complete_directories)'", bin),
+    ];
+    for flag in FLAGS {
+        let mut line = format!("complete -c {} -l {}", bin, flag.long.trim_start_matches("--"));
+        if let Some(c) = flag.short {
+            line.push_str(&format!(" -s {}", c));
+        }
+        match flag.value {
+            Value::None => {}
+            Value::File => line.push_str(" -r -F"),
+            Value::Dir => line.push_str(" -x -a '(__fish_complete_directories)'"),
+            Value::Choice(words) => line.push_str(&format!(" -x -a '{}'", words.join(" "))),
+            Value::Other(_) => line.push_str(" -x"),
+        }
+        line.push_str(&format!(" -d '{}'", flag.help.replace('\'', "\\'")));
+        lines.push(line);
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_script_covers_every_flag() {
+        for shell in SHELLS {
+            let script = generate(shell, "file_organizer").unwrap();
+            for flag in FLAGS {
+                assert!(script.contains(flag.long.trim_start_matches('-')), "{} lacks {}", shell, flag.long);
+            }
+        }
+        assert!(generate("powershell", "file_organizer").is_err());
+
+        let help = options_help();
+        assert_eq!(help.lines().count(), FLAGS.len());
+        assert!(help.lines().any(|line| line.starts_with("  -o, --output DIR ")));
+    }
+}
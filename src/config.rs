@@ -0,0 +1,347 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::categories::SubcategoryMap;
+
+/// User configuration loaded with `--config`.
+///
+/// TOML:
+/// ```toml
+/// priority = ["Code", "Images"]
+///
+/// [categories]
+/// Images = ["jpg", "png"]
+/// Ebooks = ["epub", "mobi"]
+///
+/// [subcategories.Code]
+/// Rust = ["rs"]
+///
+/// [folder_names]
+/// Images = "Bilder"
+///
+/// [destinations]
+/// Videos = "/mnt/media/Videos"
+///
+/// [profiles.photos]
+/// output = "/home/me/Pictures/Sorted"
+/// recursive = true
+/// by_date = true
+///
+/// [profiles.photos.categories]
+/// Images = ["jpg", "png", "heic"]
+/// ```
+///
+/// JSON uses the same shape: `{ "categories": { "Images": ["jpg", "png"] } }`.
+/// Unknown keys are errors, so a misspelled setting isn't silently ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub categories: HashMap<String, Vec<String>>,
+    /// Subcategories for `--subcategorize`; `None` keeps the built-in ones.
+    pub subcategories: Option<SubcategoryMap>,
+    /// Folder names for categories, e.g. to localize them; categories not
+    /// listed use their own name.
+    #[serde(default)]
+    pub folder_names: HashMap<String, String>,
+    /// Folders some categories go to instead of one in the output folder;
+    /// relative paths are relative to the output folder.
+    #[serde(default)]
+    pub destinations: HashMap<String, PathBuf>,
+    /// Categories that win, in this order, when an extension is listed in
+    /// more than one.
+    #[serde(default)]
+    pub priority: Vec<String>,
+    /// Named sets of settings picked with `--profile`.
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// A `[profiles.NAME]` section: what it sets replaces the top-level settings
+/// of the config, and its options are turned on as if given on the command
+/// line (which still wins for `output`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub categories: HashMap<String, Vec<String>>,
+    pub subcategories: Option<SubcategoryMap>,
+    #[serde(default)]
+    pub folder_names: HashMap<String, String>,
+    #[serde(default)]
+    pub destinations: HashMap<String, PathBuf>,
+    #[serde(default)]
+    pub priority: Vec<String>,
+    /// Output folder, like `--output`.
+    pub output: Option<PathBuf>,
+    #[serde(default)]
+    pub recursive: bool,
+    /// Like `--move`.
+    #[serde(default, rename = "move")]
+    pub move_files: bool,
+    #[serde(default)]
+    pub by_date: bool,
+    #[serde(default)]
+    pub dedup: bool,
+}
+
+impl Config {
+    /// Profile names, sorted.
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Applies profile `name`: its categories, subcategories, folder names,
+    /// destinations and priority replace the top-level ones where it has
+    /// any. Returns the profile for its options.
+    pub fn apply_profile(&mut self, name: &str) -> Result<Profile, String> {
+        let Some(mut profile) = self.profiles.remove(name) else {
+            let known = self.profile_names().join(", ");
+            return Err(if known.is_empty() {
+                format!("unknown profile '{}': the config has no [profiles]", name)
+            } else {
+                format!("unknown profile '{}' (the config has {})", name, known)
+            });
+        };
+        if !profile.categories.is_empty() {
+            self.categories = std::mem::take(&mut profile.categories);
+        }
+        if profile.subcategories.is_some() {
+            self.subcategories = profile.subcategories.take();
+        }
+        if !profile.folder_names.is_empty() {
+            self.folder_names = std::mem::take(&mut profile.folder_names);
+        }
+        if !profile.destinations.is_empty() {
+            self.destinations = std::mem::take(&mut profile.destinations);
+        }
+        if !profile.priority.is_empty() {
+            self.priority = std::mem::take(&mut profile.priority);
+        }
+        Ok(profile)
+    }
+}
+
+/// Reads and parses a config file. Files ending in `.json` are parsed as JSON,
+/// everything else as TOML. Extensions are normalized to lowercase without a
+/// leading dot so they match `file_extension_lowercase`.
+pub fn load_config(path: &Path) -> Result<Config, String> {
+    load_config_with_case(path, false)
+}
+
+/// Like [`load_config`], but with `case_sensitive` extensions keep the casing
+/// they were written with (for `--case-sensitive`).
+pub fn load_config_with_case(path: &Path, case_sensitive: bool) -> Result<Config, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("could not read config '{}': {}", path.display(), e))?;
+
+    let is_json = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+
+    let mut config: Config = if is_json {
+        serde_json::from_str(&text).map_err(|e| format!("invalid JSON in '{}': {}", path.display(), e))?
+    } else {
+        toml::from_str(&text).map_err(|e| format!("invalid TOML in '{}': {}", path.display(), e))?
+    };
+
+    normalize_extensions(&mut config.categories, &mut config.subcategories, case_sensitive);
+    for profile in config.profiles.values_mut() {
+        normalize_extensions(&mut profile.categories, &mut profile.subcategories, case_sensitive);
+    }
+
+    Ok(config)
+}
+
+fn normalize_extensions(
+    categories: &mut HashMap<String, Vec<String>>,
+    subcategories: &mut Option<SubcategoryMap>,
+    case_sensitive: bool,
+) {
+    let sub_exts = subcategories.iter_mut().flat_map(|subs| subs.values_mut()).flat_map(|s| s.values_mut());
+    for exts in categories.values_mut().chain(sub_exts) {
+        for ext in exts.iter_mut() {
+            let trimmed = ext.trim_start_matches('.');
+            *ext = if case_sensitive { trimmed.to_string() } else { trimmed.to_lowercase() };
+        }
+    }
+}
+
+/// Where a personal config is looked for when `--config` isn't given:
+/// `$XDG_CONFIG_HOME/file-organizer/config.toml`, or without that variable
+/// `~/.config` on Linux, `~/Library/Application Support` on macOS and
+/// `%APPDATA%` on Windows. `None` if there's no home folder to look in.
+pub fn default_config_path() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let dir = if let Some(dir) = var("XDG_CONFIG_HOME") {
+        dir
+    } else if cfg!(windows) {
+        var("APPDATA")?
+    } else if cfg!(target_os = "macos") {
+        var("HOME")?.join("Library/Application Support")
+    } else {
+        var("HOME")?.join(".config")
+    };
+    Some(dir.join("file-organizer").join("config.toml"))
+}
+
+/// Checks that every folder name belongs to a known category (including
+/// "Others" and `extra`, such as "Hidden"), is usable as a single folder, and
+/// that no two categories would end up in the same folder.
+pub fn validate_folder_names(
+    categories: &HashMap<String, Vec<String>>,
+    folder_names: &HashMap<String, String>,
+    extra: &[&str],
+) -> Result<(), String> {
+    let all = known_categories(categories, extra);
+    for (category, name) in folder_names {
+        if !all.contains(&category.as_str()) {
+            return Err(format!("folder name given for unknown category '{}'", category));
+        }
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(format!("invalid folder name '{}' for category '{}'", name, category));
+        }
+    }
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for category in all {
+        let folder = folder_names.get(category).map_or(category, |n| n.as_str());
+        if let Some(other) = seen.insert(folder, category) {
+            return Err(format!("categories '{}' and '{}' would both use the folder '{}'", other, category, folder));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every destination belongs to a known category (see
+/// [`validate_folder_names`]) and isn't empty.
+pub fn validate_destinations(
+    categories: &HashMap<String, Vec<String>>,
+    destinations: &HashMap<String, PathBuf>,
+    extra: &[&str],
+) -> Result<(), String> {
+    let all = known_categories(categories, extra);
+    for (category, dest) in destinations {
+        if !all.contains(&category.as_str()) {
+            return Err(format!("destination given for unknown category '{}'", category));
+        }
+        if dest.as_os_str().is_empty() {
+            return Err(format!("empty destination for category '{}'", category));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every category in a priority list exists and is named once.
+pub fn validate_priority(categories: &HashMap<String, Vec<String>>, priority: &[String]) -> Result<(), String> {
+    for (i, category) in priority.iter().enumerate() {
+        if !categories.contains_key(category) {
+            return Err(format!("priority given for unknown category '{}'", category));
+        }
+        if priority[..i].contains(category) {
+            return Err(format!("category '{}' is in the priority list twice", category));
+        }
+    }
+    Ok(())
+}
+
+/// Adds a `--map` value like `jpg,png=Pictures` to `maps` (category ->
+/// extensions), with the extensions normalized like a config file's. An
+/// extension an earlier value gave to another category is an error.
+pub fn add_mapping(maps: &mut HashMap<String, Vec<String>>, spec: &str, case_sensitive: bool) -> Result<(), String> {
+    let usage = || format!("invalid --map '{}' (expected EXTENSIONS=CATEGORY, e.g. jpg,png=Pictures)", spec);
+    let (exts, category) = spec.split_once('=').ok_or_else(usage)?;
+    let category = category.trim();
+    let exts: Vec<String> = exts
+        .split(',')
+        .map(|ext| ext.trim().trim_start_matches('.'))
+        .map(|ext| if case_sensitive { ext.to_string() } else { ext.to_lowercase() })
+        .collect();
+    if exts.iter().any(|ext| ext.is_empty())
+        || category.is_empty()
+        || category == "."
+        || category == ".."
+        || category.contains(['/', '\\'])
+    {
+        return Err(usage());
+    }
+    for ext in exts {
+        if let Some((other, _)) = maps.iter().find(|(other, others)| *other != category && others.contains(&ext)) {
+            return Err(format!("--map gives '{}' to both '{}' and '{}'", ext, other, category));
+        }
+        let entry = maps.entry(category.to_string()).or_default();
+        if !entry.contains(&ext) {
+            entry.push(ext);
+        }
+    }
+    Ok(())
+}
+
+/// Names of all categories plus "Others" and `extra`, sorted.
+fn known_categories<'a>(categories: &'a HashMap<String, Vec<String>>, extra: &[&'a str]) -> Vec<&'a str> {
+    let mut all: Vec<&str> = categories.keys().map(|k| k.as_str()).chain(["Others"]).collect();
+    all.extend(extra);
+    all.sort();
+    all
+}
+
+/// Layers `user` subcategories on top of `base`, category by category, with
+/// the same rules as [`merge_categories`].
+pub fn merge_subcategories(
+    mut base: SubcategoryMap,
+    user: SubcategoryMap,
+) -> SubcategoryMap {
+    for (cat, subs) in user {
+        let merged = merge_categories(base.remove(&cat).unwrap_or_default(), subs);
+        base.insert(cat, merged);
+    }
+    base
+}
+
+/// Layers `user` categories on top of `base`. Extensions claimed by a user
+/// category are removed from every other category so the user's choice wins.
+pub fn merge_categories(
+    mut base: HashMap<String, Vec<String>>,
+    user: HashMap<String, Vec<String>>,
+) -> HashMap<String, Vec<String>> {
+    for (cat, exts) in &user {
+        for (other, other_exts) in base.iter_mut() {
+            if other != cat {
+                other_exts.retain(|e| !exts.contains(e));
+            }
+        }
+    }
+    for (cat, exts) in user {
+        let entry = base.entry(cat).or_default();
+        for ext in exts {
+            if !entry.contains(&ext) {
+                entry.push(ext);
+            }
+        }
+    }
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn misspelled_keys_are_errors_at_every_level() {
+        let dir = tempfile::tempdir().unwrap();
+        let load = |name: &str, text: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, text).unwrap();
+            load_config(&path)
+        };
+        assert!(load("ok.toml", "priority = [\"Images\"]\n[folder_names]\nImages = \"Bilder\"\n").is_ok());
+        assert!(load("top.toml", "priorty = [\"Images\"]\n").unwrap_err().contains("priorty"));
+        assert!(load("table.toml", "[folder_name]\nImages = \"Bilder\"\n").unwrap_err().contains("folder_name"));
+        assert!(load("profile.toml", "[profiles.p]\nrecursiv = true\n").unwrap_err().contains("recursiv"));
+        assert!(load("top.json", r#"{"categorys": {}}"#).unwrap_err().contains("categorys"));
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use std::fmt;
+use std::fs::Metadata;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// Default `--date-format`: one folder per year, one subfolder per month.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y/%m";
+
+/// Folder used when a file's timestamp can't be read.
+pub const UNKNOWN_DATE_FOLDER: &str = "Unknown-Date";
+
+/// Which timestamp of a file `--by-date` sorts by (`--date-field`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DateField {
+    /// When the file was created; not every platform or filesystem records it.
+    Created,
+    #[default]
+    Modified,
+    Accessed,
+}
+
+impl DateField {
+    /// The timestamp this field names, read from `meta`.
+    pub fn of(self, meta: &Metadata) -> io::Result<SystemTime> {
+        match self {
+            DateField::Created => meta.created(),
+            DateField::Modified => meta.modified(),
+            DateField::Accessed => meta.accessed(),
+        }
+    }
+}
+
+impl FromStr for DateField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(DateField::Created),
+            "modified" => Ok(DateField::Modified),
+            "accessed" => Ok(DateField::Accessed),
+            _ => Err(format!("invalid --date-field '{}' (expected created, modified or accessed)", s)),
+        }
+    }
+}
+
+impl fmt::Display for DateField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateField::Created => write!(f, "created"),
+            DateField::Modified => write!(f, "modified"),
+            DateField::Accessed => write!(f, "accessed"),
+        }
+    }
+}
+
+/// Checks that `format` is a usable strftime pattern, e.g. `%Y/%m` or `%Y-%m-%d`.
+pub fn validate_date_format(format: &str) -> Result<(), String> {
+    if format.trim().is_empty() {
+        return Err("date format must not be empty".to_string());
+    }
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid date format '{}'", format));
+    }
+    Ok(())
+}
+
+/// Which end of a `--since` / `--until` window a date is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The window starts at the beginning of the day.
+    Start,
+    /// The window ends at the end of the day, so the day itself is included.
+    End,
+}
+
+/// Parses a `--since` / `--until` value: a date like `2024-06-30` (local
+/// time, the whole day included) or an age like `7d`, `12h` or `2w` counted
+/// back from `now`.
+pub fn parse_date_bound(input: &str, bound: Bound, now: SystemTime) -> Result<SystemTime, String> {
+    let s = input.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        let day = match bound {
+            Bound::Start => Some(date),
+            Bound::End => date.succ_opt(),
+        };
+        let midnight = day
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .and_then(|d| Local.from_local_datetime(&d).earliest())
+            .ok_or_else(|| format!("invalid date '{}'", input))?;
+        let time = SystemTime::from(midnight);
+        return Ok(match bound {
+            Bound::Start => time,
+            // The last moment of the day, so `--until` stays inclusive
+            Bound::End => time - Duration::from_nanos(1),
+        });
+    }
+
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let seconds: u64 = match unit.to_ascii_lowercase().as_str() {
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("invalid date '{}': expected YYYY-MM-DD or an age like 7d", input)),
+    };
+    let count: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid date '{}': expected YYYY-MM-DD or an age like 7d", input))?;
+    count
+        .checked_mul(seconds)
+        .and_then(|secs| now.checked_sub(Duration::from_secs(secs)))
+        .ok_or_else(|| format!("invalid date '{}': too far back", input))
+}
+
+/// Relative folder path for `time` rendered with `format` in local time.
+/// `/` in the format creates nested folders, so `%Y/%m` gives `2023/06`.
+pub fn date_folder(time: SystemTime, format: &str) -> PathBuf {
+    let local: DateTime<Local> = time.into();
+    local
+        .format(format)
+        .to_string()
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_bounds_cover_the_whole_day() {
+        let now = SystemTime::now();
+        let start = parse_date_bound("2024-06-30", Bound::Start, now).unwrap();
+        let end = parse_date_bound("2024-06-30", Bound::End, now).unwrap();
+        let day = end.duration_since(start).unwrap();
+        assert!(day >= Duration::from_secs(23 * 3600) && day < Duration::from_secs(25 * 3600));
+
+        let week = parse_date_bound("7d", Bound::Start, now).unwrap();
+        assert_eq!(now.duration_since(week).unwrap(), Duration::from_secs(7 * 24 * 3600));
+        assert_eq!(parse_date_bound("2w", Bound::End, now).unwrap(), now - Duration::from_secs(14 * 24 * 3600));
+        assert!(parse_date_bound("yesterday", Bound::Start, now).is_err());
+        assert!(parse_date_bound("2024-13-01", Bound::Start, now).is_err());
+        assert!(parse_date_bound("d", Bound::Start, now).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! `--exec CMD`: runs a command after each file is placed, e.g. to upload it
+//! or record it somewhere: `--exec "upload --to {category} {dest}"`.
+//!
+//! Placeholders: `{source}` (where the file was), `{dest}` (where it is now)
+//! and `{category}`. By default the command is split into words (single or
+//! double quotes group words) and run directly, so a file name is always one
+//! argument and never reaches a shell. `--exec-shell` runs it with `sh -c`
+//! instead, for pipes and redirects; the substituted values are quoted.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A command to run for each placed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    /// The command's words, placeholders not yet filled in.
+    words: Vec<String>,
+    /// Run through `sh -c` rather than directly.
+    shell: bool,
+}
+
+impl Hook {
+    const PLACEHOLDERS: [&'static str; 3] = ["source", "dest", "category"];
+
+    /// Parses a command template (see the module docs).
+    pub fn parse(template: &str, shell: bool) -> Result<Self, String> {
+        let invalid = |why: &str| format!("invalid --exec command '{}': {}", template, why);
+        let words = if shell { vec![template.to_string()] } else { split_words(template).map_err(invalid)? };
+        if words.iter().all(|w| w.trim().is_empty()) {
+            return Err(invalid("it is empty"));
+        }
+        for word in &words {
+            fill(word, |name| Self::PLACEHOLDERS.contains(&name).then(String::new)).map_err(invalid)?;
+        }
+        Ok(Hook { words, shell })
+    }
+
+    /// Runs the command for `source`, now at `dest` in `category`. Its output
+    /// goes to stderr so it can't mix with a `--json` report.
+    pub fn run(&self, source: &Path, dest: &Path, category: &str) -> Result<(), String> {
+        let value = |name: &str| {
+            let value = match name {
+                "source" => source.to_string_lossy().into_owned(),
+                "dest" => dest.to_string_lossy().into_owned(),
+                "category" => category.to_string(),
+                _ => return None,
+            };
+            Some(if self.shell { sh_quote(&value) } else { value })
+        };
+        // Checked by `parse`
+        let words: Vec<String> = self.words.iter().map(|word| fill(word, value).unwrap_or_default()).collect();
+        let mut command = if self.shell {
+            let mut command = Command::new("sh");
+            command.arg("-c").args(&words);
+            command
+        } else {
+            let mut command = Command::new(&words[0]);
+            command.args(&words[1..]);
+            command
+        };
+        let status = command
+            .stdin(Stdio::null())
+            .stdout(io::stderr())
+            .status()
+            .map_err(|e| format!("could not run --exec command '{}': {}", words[0], e))?;
+        match status.code() {
+            _ if status.success() => Ok(()),
+            Some(code) => Err(format!("--exec command failed with exit code {}", code)),
+            None => Err("--exec command was killed".to_string()),
+        }
+    }
+}
+
+/// `word` with each `{name}` replaced by `value(name)`, in one pass so a
+/// value that itself contains braces is left alone.
+fn fill(word: &str, value: impl Fn(&str) -> Option<String>) -> Result<String, &'static str> {
+    let mut filled = String::new();
+    let mut rest = word;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            return Err("unmatched '{'");
+        };
+        let name = &rest[open + 1..open + close];
+        let Some(value) = value(name) else {
+            return Err("unknown placeholder (expected {source}, {dest} or {category})");
+        };
+        filled.push_str(&rest[..open]);
+        filled.push_str(&value);
+        rest = &rest[open + close + 1..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+/// Splits on whitespace, keeping what's inside single or double quotes
+/// together. Backslashes are kept as they are, for Windows paths.
+fn split_words(command: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.get_or_insert_default().push(c),
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            None if c.is_whitespace() => words.extend(word.take()),
+            None => word.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("unclosed quote");
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// `value` as one `sh` word.
+pub(crate) fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_split_into_words_and_fill_placeholders() {
+        let words = split_words(r#"cp  "{dest}" 'a b'/{category} x"y"z"#).unwrap();
+        assert_eq!(words, ["cp", "{dest}", "a b/{category}", "xyz"]);
+        assert_eq!(split_words("echo ''").unwrap(), ["echo", ""]);
+        assert!(split_words("echo 'oops").is_err());
+
+        let value = |name: &str| (name == "dest").then(|| "{category}".to_string());
+        assert_eq!(fill("--to={dest}!", value).unwrap(), "--to={category}!");
+        assert!(fill("{nope}", value).is_err());
+        assert!(Hook::parse("upload {dest", false).is_err());
+        assert!(Hook::parse("  ", false).is_err());
+
+        assert_eq!(sh_quote("it's"), r"'it'\''s'");
+    }
+}
@@ -0,0 +1,141 @@
+//! `--by-exif-date`: the date a photo was taken, from the EXIF
+//! `DateTimeOriginal` tag, for sorting photos whose file timestamps were
+//! reset by copying or cloud sync.
+//!
+//! Only as much of the format is read as needed to find that tag. JPEG and
+//! HEIC files carry the EXIF block (`Exif\0\0` followed by a TIFF header)
+//! near the start of the file; TIFF-based raw formats are a TIFF header
+//! themselves.
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Extensions of files that can carry EXIF data (compared case-insensitively).
+pub const EXIF_EXTENSIONS: [&str; 11] =
+    ["jpg", "jpeg", "jpe", "heic", "heif", "tif", "tiff", "dng", "cr2", "nef", "arw"];
+
+/// How much of a file is searched for the EXIF block.
+const SEARCH_LIMIT: u64 = 256 * 1024;
+
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+
+/// When the photo at `path` was taken according to its EXIF data, read as
+/// local time. `None` if the file has no (readable) EXIF date.
+pub fn date_taken(path: &Path) -> Option<SystemTime> {
+    let mut data = Vec::new();
+    File::open(path).ok()?.take(SEARCH_LIMIT).read_to_end(&mut data).ok()?;
+    let taken = exif_date(&data)?;
+    Local.from_local_datetime(&taken).earliest().map(SystemTime::from)
+}
+
+/// Finds the EXIF block in the start of a file and reads its date.
+pub fn exif_date(data: &[u8]) -> Option<NaiveDateTime> {
+    if is_tiff_header(data) {
+        return tiff_date(data);
+    }
+    let start = data.windows(6).position(|w| w == b"Exif\0\0")? + 6;
+    tiff_date(&data[start..])
+}
+
+fn is_tiff_header(data: &[u8]) -> bool {
+    data.starts_with(b"II*\0") || data.starts_with(b"MM\0*")
+}
+
+/// Reads `DateTimeOriginal` (or `DateTimeDigitized`) from a TIFF structure.
+fn tiff_date(tiff: &[u8]) -> Option<NaiveDateTime> {
+    if !is_tiff_header(tiff) {
+        return None;
+    }
+    let reader = Reader { data: tiff, little_endian: tiff[0] == b'I' };
+    let ifd0 = reader.u32(4)? as usize;
+    let exif_ifd = reader.find_tag(ifd0, TAG_EXIF_IFD)?;
+    let exif_offset = reader.u32(exif_ifd + 8)? as usize;
+    [TAG_DATE_TIME_ORIGINAL, TAG_DATE_TIME_DIGITIZED]
+        .into_iter()
+        .find_map(|tag| reader.find_tag(exif_offset, tag).and_then(|entry| reader.ascii_date(entry)))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(at..at.checked_add(2)?)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(at..at.checked_add(4)?)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    /// Offset of the 12-byte entry for `tag` in the IFD at `ifd`.
+    fn find_tag(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = self.u16(ifd)? as usize;
+        (0..count).map(|i| ifd + 2 + i * 12).find(|&entry| self.u16(entry) == Some(tag))
+    }
+
+    /// An ASCII entry holding a date like `2023:06:14 18:30:05`.
+    fn ascii_date(&self, entry: usize) -> Option<NaiveDateTime> {
+        const ASCII: u16 = 2;
+        if self.u16(entry + 2)? != ASCII {
+            return None;
+        }
+        let len = self.u32(entry + 4)? as usize;
+        // Values of up to four bytes are stored inline; a date never is
+        let at = self.u32(entry + 8)? as usize;
+        let text = self.data.get(at..at.checked_add(len)?)?;
+        let text = std::str::from_utf8(text).ok()?.trim_end_matches('\0').trim();
+        NaiveDateTime::parse_from_str(text, "%Y:%m:%d %H:%M:%S").ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A little-endian TIFF block with an EXIF IFD holding `date`.
+    fn tiff_with_date(date: &str) -> Vec<u8> {
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8u32.to_le_bytes());
+        // IFD0 at 8: one entry pointing at the EXIF IFD at 26
+        tiff.extend(1u16.to_le_bytes());
+        tiff.extend(TAG_EXIF_IFD.to_le_bytes());
+        tiff.extend(4u16.to_le_bytes());
+        tiff.extend(1u32.to_le_bytes());
+        tiff.extend(26u32.to_le_bytes());
+        tiff.extend(0u32.to_le_bytes());
+        // EXIF IFD at 26: DateTimeOriginal, its text at 44
+        tiff.extend(1u16.to_le_bytes());
+        tiff.extend(TAG_DATE_TIME_ORIGINAL.to_le_bytes());
+        tiff.extend(2u16.to_le_bytes());
+        tiff.extend((date.len() as u32 + 1).to_le_bytes());
+        tiff.extend(44u32.to_le_bytes());
+        tiff.extend(0u32.to_le_bytes());
+        tiff.extend(date.as_bytes());
+        tiff.push(0);
+        tiff
+    }
+
+    #[test]
+    fn reads_the_date_a_photo_was_taken() {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x40];
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(tiff_with_date("2023:06:14 18:30:05"));
+        let expected = NaiveDateTime::parse_from_str("2023-06-14 18:30:05", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(exif_date(&jpeg), Some(expected));
+        assert_eq!(exif_date(&tiff_with_date("2023:06:14 18:30:05")), Some(expected));
+
+        assert_eq!(exif_date(&tiff_with_date("0000:00:00 00:00:00")), None);
+        assert_eq!(exif_date(b"\xFF\xD8\xFF\xE0 no exif here"), None);
+        // Cut off in the middle of the EXIF IFD
+        assert_eq!(exif_date(&tiff_with_date("2023:06:14 18:30:05")[..30]), None);
+    }
+}
@@ -0,0 +1,58 @@
+//! `.organizerignore`: glob patterns, one per line, for files (and folders)
+//! of the organized folder that should always be left alone. Blank lines and
+//! lines starting with `#` are skipped. Patterns are matched against names,
+//! like `--exclude`.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Name of the ignore file looked for in the organized folder.
+pub const IGNORE_FILE: &str = ".organizerignore";
+
+/// Reads the ignore file in `dir`. No file means no patterns; when there
+/// is one, the ignore file itself is always left alone too.
+pub fn load_ignore_file(dir: &Path) -> Result<Vec<glob::Pattern>, String> {
+    let path = dir.join(IGNORE_FILE);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("could not read '{}': {}", path.display(), e)),
+    };
+    let mut patterns = parse_ignore(&text).map_err(|e| format!("{} in '{}'", e, path.display()))?;
+    patterns.push(glob::Pattern::new(&glob::Pattern::escape(IGNORE_FILE)).expect("escaped name is a valid pattern"));
+    Ok(patterns)
+}
+
+/// Parses the contents of an ignore file.
+pub fn parse_ignore(text: &str) -> Result<Vec<glob::Pattern>, String> {
+    let mut patterns = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let pattern = glob::Pattern::new(line)
+            .map_err(|e| format!("invalid pattern '{}' on line {}: {}", line, i + 1, e))?;
+        patterns.push(pattern);
+    }
+    Ok(patterns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let patterns = parse_ignore("# system files\ndesktop.ini\n\n   \n*.part\n").unwrap();
+        let patterns: Vec<&str> = patterns.iter().map(|p| p.as_str()).collect();
+        assert_eq!(patterns, ["desktop.ini", "*.part"]);
+    }
+
+    #[test]
+    fn reports_the_line_of_a_bad_pattern() {
+        let err = parse_ignore("ok.txt\n[oops\n").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+    }
+}
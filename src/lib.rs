@@ -0,0 +1,1086 @@
+//! Core of the file organizer: sorts the files of a folder into category
+//! folders (Images, Documents, Videos, ...) based on their extension.
+//!
+//! The `file_organizer` binary is a thin command-line wrapper around
+//! [`organize`]; other programs can call it directly.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub mod action;
+pub mod archive;
+pub mod categories;
+pub mod config;
+pub mod dates;
+pub mod exec;
+pub mod exif;
+pub mod hash;
+pub mod ignore;
+pub mod metadata;
+pub mod oplog;
+pub mod owner;
+pub mod plan;
+pub mod prefix;
+pub mod rename;
+pub mod report;
+pub mod rules;
+pub mod script;
+pub mod size;
+pub mod space;
+pub mod state;
+pub mod tag;
+pub mod sniff;
+pub mod stats;
+pub mod transfer;
+pub mod trash;
+pub mod undo;
+
+mod run;
+
+pub use action::{ActionStatus, Change, Conflict, Failure, FailureKind, FileAction, SkipReason};
+pub use categories::{
+    COMPOUND_EXTENSIONS, DEFAULT_SMALL_THRESHOLD, ExtensionIndex, HIDDEN_CATEGORY, HiddenFiles, OthersPolicy,
+    SMALL_FILES_CATEGORY, SubcategoryMap,
+    build_category_map, build_subcategory_map, builtin_categories, category_for_extension, category_for_extension_with,
+    category_for_mime, file_extension, file_extension_lowercase, is_hidden, subcategory_for_extension,
+};
+pub use size::{format_size, parse_size};
+pub use transfer::{
+    ConflictFormat, ConflictPolicy, LinkKind, copy_file_to_category, copy_file_to_category_with_retries, copy_new,
+    copy_times, copy_to_unique_destination, explain_path_error, link_new, move_file_to_category, unique_destination,
+    windows_safe_name,
+};
+
+/// Summary buckets that count outcomes rather than files placed into a category.
+/// Listed after "Others" in the summary, in this order, when present.
+/// Renamed and overwritten files are also counted in their category, as are
+/// empty files that were placed. Folders and symlinks that were passed over,
+/// files that were already organized, empty files and moves that were rolled
+/// back only get a row when there were any (empty files always do with
+/// `skip_empty`, unchanged files with `since_last_run`).
+const STATUS_BUCKETS: [&str; 13] = [
+    "Renamed",
+    "Overwritten",
+    "Kept newer",
+    "Unmatched",
+    "Already organized",
+    "Skipped",
+    "Dirs skipped",
+    "Symlinks skipped",
+    "Empty files",
+    "Unchanged",
+    "Rolled back",
+    "Duplicates",
+    "Errors",
+];
+
+/// Which subfolders of the organized folder are deleted after a move once
+/// they are empty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyDirs {
+    /// None.
+    #[default]
+    Keep,
+    /// Only those that held files this run moved away (`--prune-empty-dirs`).
+    Emptied,
+    /// Every empty one, even if it was empty before the run (`--prune-all-empty`).
+    All,
+}
+
+/// Settings for a single [`organize`] run.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Only report what would happen; don't touch any files.
+    pub dry_run: bool,
+    /// In a dry run, compare each file with the one already at its destination
+    /// (see [`Change`]).
+    pub diff: bool,
+    /// Move files instead of copying them.
+    pub move_files: bool,
+    /// Link files into their category instead of copying them (not with
+    /// `move_files`). Counted as copies in the summary.
+    pub link: Option<LinkKind>,
+    /// Descend into subfolders.
+    pub recursive: bool,
+    /// How many levels of subfolders to descend into (`None` = unlimited).
+    pub max_depth: Option<usize>,
+    /// After a move, delete subfolders of the organized folder that ended up empty.
+    pub remove_empty_dirs: EmptyDirs,
+    /// Recreate each file's subfolder path (relative to the organized folder)
+    /// inside its category, e.g. `Images/2023/trip/photo.jpg`.
+    pub keep_structure: bool,
+    /// Skip files whose contents were already placed into the same category this run.
+    pub dedup: bool,
+    /// Put everything in a folder per owning user first, e.g. `alice/Images`
+    /// (Unix only, see [`owner`]).
+    pub by_owner: bool,
+    /// Sort into date folders (see `date_format`) based on each file's modified time.
+    pub by_date: bool,
+    /// With `by_date`, use only the date folders instead of nesting them under categories.
+    pub date_only: bool,
+    /// strftime pattern for date folders; `/` separates nested folders.
+    pub date_format: String,
+    /// Which timestamp date folders come from; created times fall back to
+    /// modified where they aren't recorded.
+    pub date_field: dates::DateField,
+    /// Date photos by when they were taken (see [`exif`]), falling back to
+    /// `date_field` for other files.
+    pub by_exif_date: bool,
+    /// Append a record of every copy/move to this file (see [`oplog`]).
+    pub log: Option<PathBuf>,
+    /// Command run after each file is placed (see [`exec`]).
+    pub exec: Option<exec::Hook>,
+    /// Only organize files whose name matches one of these (empty = all files).
+    pub include: Vec<glob::Pattern>,
+    /// Never organize files whose name matches one of these; wins over `include`.
+    pub exclude: Vec<glob::Pattern>,
+    /// Patterns from `.organizerignore` (see [`ignore`]); matching files and
+    /// folders are left alone.
+    pub ignore: Vec<glob::Pattern>,
+    /// Leave empty (zero-byte) files where they are.
+    pub skip_empty: bool,
+    /// Skip files smaller than this many bytes.
+    pub min_size: Option<u64>,
+    /// Skip files larger than this many bytes.
+    pub max_size: Option<u64>,
+    /// Skip files last modified before this time (`--since`).
+    pub modified_since: Option<SystemTime>,
+    /// Skip files last modified after this time (`--until`).
+    pub modified_until: Option<SystemTime>,
+    /// Only look at files modified since the last run over the folder that
+    /// went without errors (see [`state`]).
+    pub since_last_run: bool,
+    /// Organize what symlinks point to instead of skipping them.
+    pub follow_symlinks: bool,
+    /// Whether dotfiles are sorted, skipped or kept in a "Hidden" category.
+    pub hidden: HiddenFiles,
+    /// Put files smaller than this many bytes into [`SMALL_FILES_CATEGORY`]
+    /// instead of their own category (hidden files still go to "Hidden").
+    pub small_files: Option<u64>,
+    /// Match extensions exactly instead of ignoring case, so `photo.JPG` is
+    /// only an image if a category lists `JPG`.
+    pub case_sensitive: bool,
+    /// Classify files with a missing or unknown extension by their first
+    /// bytes (see [`sniff`]).
+    pub sniff_content: bool,
+    /// Classify files by the MIME type of their contents (see [`sniff`]),
+    /// falling back to the extension when the contents aren't recognized.
+    pub by_mime: bool,
+    /// Give copies the accessed/modified times of their source.
+    pub preserve_times: bool,
+    /// Give copies the owner, group and extended attributes of their source
+    /// where the platform and permissions allow (see [`metadata`]).
+    pub preserve_metadata: bool,
+    /// Compare every copy with its source after copying (not with `move_files`).
+    pub verify: bool,
+    /// What to do when the destination name is already taken.
+    pub on_conflict: ConflictPolicy,
+    /// With `on_conflict` overwrite, move the replaced files to the trash (see [`trash`]).
+    pub trash_on_overwrite: bool,
+    /// How renamed files are named (with [`ConflictPolicy::Rename`]).
+    pub conflict_format: ConflictFormat,
+    /// Rewrites applied to file names as they are placed (see [`rename`]).
+    pub rename: Vec<rename::RenameRule>,
+    /// Give destination names the composed (NFC) Unicode form, so `café`
+    /// written decomposed (as macOS does) and composed end up the same name.
+    pub normalize_unicode: bool,
+    /// Sort the files inside `.zip` archives rather than the archives (see
+    /// [`archive`]).
+    pub extract_archives: bool,
+    /// With `extract_archives`, unpack zips found inside zips too.
+    pub extract_nested: bool,
+    /// Stop after this many files were copied or moved (or would be, or
+    /// failed to be); skipped files don't count.
+    pub limit: Option<usize>,
+    /// End the run at the first error instead of carrying on.
+    pub fail_fast: bool,
+    /// How many times a copy or move that fails with a transient error (a
+    /// network drive timing out) is tried again (see [`transfer::with_retries`]).
+    pub retries: u32,
+    /// With `move_files`, end the run at the first error and move every file
+    /// it moved back to where it was.
+    pub transactional: bool,
+    /// Keep every action in [`Summary::actions`]. Without it only failed
+    /// files and files with a warning are kept, and the rest are just
+    /// counted: for callers that get each action as it happens (see
+    /// [`organize_with_callbacks`]), so a huge folder doesn't pile them all up.
+    /// Moves are kept anyway when `transactional` or
+    /// [`EmptyDirs::Emptied`] need them afterwards.
+    pub keep_actions: bool,
+    /// Number of worker threads copying files (1 = sequential).
+    pub jobs: usize,
+    /// Folder the category folders are created in (`None` = the organized folder itself).
+    pub output: Option<PathBuf>,
+    /// What happens to files that match no category.
+    pub others: OthersPolicy,
+    /// Folder names for categories that shouldn't be named after themselves,
+    /// e.g. "Images" -> "Bilder". Categories keep their name everywhere else.
+    pub folder_names: HashMap<String, String>,
+    /// Folders some categories go to instead of one in the output folder,
+    /// e.g. "Videos" -> `/mnt/media/Videos`. Relative paths are relative to
+    /// the output folder.
+    pub destinations: HashMap<String, PathBuf>,
+    /// Category name -> extensions, as produced by [`build_category_map`].
+    pub categories: HashMap<String, Vec<String>>,
+    /// Rules tried before the extension, first match wins (see [`rules`]).
+    /// Their categories should be in `categories` too.
+    pub rules: Vec<rules::Rule>,
+    /// Categories that win, in this order, when an extension is listed in
+    /// more than one; the others follow in name order.
+    pub priority: Vec<String>,
+    /// Put files into a subfolder of their category, e.g. `Code/Rust`.
+    pub subcategorize: bool,
+    /// Category -> subcategory -> extensions, as produced by [`build_subcategory_map`].
+    pub subcategories: SubcategoryMap,
+    /// Group files sharing a name prefix (`IMG_...`) into a folder of that
+    /// name inside their category (see [`prefix`]).
+    pub by_prefix: bool,
+    /// How many files must share a prefix for `by_prefix` to group them.
+    pub prefix_min: usize,
+    /// Put files whose name carries a tag into a folder of that name inside
+    /// their category (see [`tag`]).
+    pub tag_pattern: Option<tag::TagPattern>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            dry_run: false,
+            diff: false,
+            move_files: false,
+            link: None,
+            recursive: false,
+            max_depth: None,
+            remove_empty_dirs: EmptyDirs::Keep,
+            keep_structure: false,
+            dedup: false,
+            by_owner: false,
+            by_date: false,
+            date_only: false,
+            date_format: dates::DEFAULT_DATE_FORMAT.to_string(),
+            date_field: dates::DateField::Modified,
+            by_exif_date: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            ignore: Vec::new(),
+            skip_empty: false,
+            min_size: None,
+            max_size: None,
+            modified_since: None,
+            modified_until: None,
+            since_last_run: false,
+            follow_symlinks: false,
+            hidden: HiddenFiles::Sort,
+            small_files: None,
+            case_sensitive: false,
+            sniff_content: false,
+            by_mime: false,
+            preserve_times: false,
+            preserve_metadata: false,
+            verify: false,
+            on_conflict: ConflictPolicy::Rename,
+            trash_on_overwrite: false,
+            conflict_format: ConflictFormat::default(),
+            rename: Vec::new(),
+            normalize_unicode: false,
+            extract_archives: false,
+            extract_nested: false,
+            fail_fast: false,
+            retries: 0,
+            transactional: false,
+            keep_actions: true,
+            limit: None,
+            jobs: 1,
+            output: None,
+            log: None,
+            exec: None,
+            others: OthersPolicy::Folder,
+            folder_names: HashMap::new(),
+            destinations: HashMap::new(),
+            categories: build_category_map(),
+            priority: Vec::new(),
+            rules: Vec::new(),
+            subcategorize: false,
+            subcategories: build_subcategory_map(),
+            by_prefix: false,
+            prefix_min: prefix::DEFAULT_PREFIX_MIN,
+            tag_pattern: None,
+        }
+    }
+}
+
+impl Options {
+    /// "move", "link" or "copy", for messages.
+    pub fn verb(&self) -> &'static str {
+        if self.move_files {
+            "move"
+        } else if self.link.is_some() {
+            "link"
+        } else {
+            "copy"
+        }
+    }
+
+    /// Name of the folder files of `category` go into (and of the category
+    /// in the summary).
+    pub fn folder_name<'a>(&'a self, category: &'a str) -> &'a str {
+        self.folder_names.get(category).map_or(category, |name| name.as_str())
+    }
+
+    /// "moved", "linked" or "copied", for messages.
+    pub fn past_tense(&self) -> &'static str {
+        if self.move_files {
+            "moved"
+        } else if self.link.is_some() {
+            "linked"
+        } else {
+            "copied"
+        }
+    }
+}
+
+/// Result of a run: per-category file counts (plus the "Others" and "Errors"
+/// buckets) and the individual action taken for every entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub counts: HashMap<String, usize>,
+    /// Bytes placed into each category (not the status buckets).
+    pub bytes: HashMap<String, u64>,
+    pub actions: Vec<FileAction>,
+    /// True if the run was ended early (e.g. the user chose to quit).
+    pub stopped: bool,
+    /// Empty subfolders deleted after the run (`remove_empty_dirs`).
+    pub removed_dirs: Vec<PathBuf>,
+    /// How long the run took.
+    pub elapsed: Duration,
+    /// How much of [`Options::limit`] the run used up: the files it copied or
+    /// moved (or would have), and those that failed to be. 0 without a limit.
+    pub limit_used: usize,
+}
+
+impl Summary {
+    fn new(options: &Options) -> Self {
+        let mut counts: HashMap<String, usize> = options.categories.keys().map(|k| (k.clone(), 0)).collect();
+        counts.insert("Others".to_string(), 0);
+        counts.insert("Errors".to_string(), 0);
+        if options.hidden == HiddenFiles::Separate {
+            counts.insert(HIDDEN_CATEGORY.to_string(), 0);
+        }
+        if options.small_files.is_some() {
+            counts.insert(SMALL_FILES_CATEGORY.to_string(), 0);
+        }
+        if options.dedup {
+            counts.insert("Duplicates".to_string(), 0);
+        }
+        if options.on_conflict.replaces() {
+            counts.insert("Overwritten".to_string(), 0);
+        }
+        if options.on_conflict == ConflictPolicy::Newest {
+            counts.insert("Kept newer".to_string(), 0);
+        }
+        if options.others == OthersPolicy::Leave {
+            counts.insert("Unmatched".to_string(), 0);
+        }
+        if options.skip_empty {
+            counts.insert("Empty files".to_string(), 0);
+        }
+        if options.since_last_run {
+            counts.insert("Unchanged".to_string(), 0);
+        }
+        if !options.include.is_empty()
+            || !options.exclude.is_empty()
+            || !options.ignore.is_empty()
+            || options.min_size.is_some()
+            || options.max_size.is_some()
+            || options.modified_since.is_some()
+            || options.modified_until.is_some()
+            || options.on_conflict != ConflictPolicy::Rename
+            || options.hidden == HiddenFiles::Skip
+        {
+            counts.insert("Skipped".to_string(), 0);
+        }
+        let bytes = counts.keys().filter(|k| !STATUS_BUCKETS.contains(&k.as_str())).map(|k| (k.clone(), 0)).collect();
+        Summary {
+            counts,
+            bytes,
+            actions: Vec::new(),
+            stopped: false,
+            removed_dirs: Vec::new(),
+            elapsed: Duration::ZERO,
+            limit_used: 0,
+        }
+    }
+
+    /// Adds an action to the summary, bumping the matching counter.
+    fn record(&mut self, action: FileAction) {
+        self.tally(&action);
+        self.actions.push(action);
+    }
+
+    /// Bumps the counters for an action without keeping it.
+    fn tally(&mut self, action: &FileAction) {
+        self.adjust_counts(action, false);
+    }
+
+    /// Takes an action that was tallied back out of the counters, before it
+    /// changes (e.g. is rolled back) and is tallied again.
+    fn untally(&mut self, action: &FileAction) {
+        self.adjust_counts(action, true);
+    }
+
+    fn adjust_counts(&mut self, action: &FileAction, undo: bool) {
+        let step = |n: &mut usize| if undo { *n = n.saturating_sub(1) } else { *n += 1 };
+        let bucket = match (&action.status, &action.category) {
+            (ActionStatus::Failed(_), _) => Some("Errors"),
+            (ActionStatus::Duplicate(_), _) => Some("Duplicates"),
+            (ActionStatus::Skipped(SkipReason::Unmatched), _) => Some("Unmatched"),
+            (ActionStatus::Skipped(SkipReason::Symlink), _) => Some("Symlinks skipped"),
+            (ActionStatus::Skipped(SkipReason::Empty), _) => Some("Empty files"),
+            (ActionStatus::Skipped(SkipReason::Unchanged), _) => Some("Unchanged"),
+            (ActionStatus::Skipped(SkipReason::NotNewer), _) => Some("Kept newer"),
+            (ActionStatus::Skipped(SkipReason::AlreadyOrganized), _) => Some("Already organized"),
+            (ActionStatus::Skipped(SkipReason::RolledBack), _) => Some("Rolled back"),
+            (ActionStatus::Skipped(reason), _) if reason.is_folder() => Some("Dirs skipped"),
+            (ActionStatus::Skipped(reason), _) if reason.counts_as_skipped() => Some("Skipped"),
+            (status, Some(category)) if status.is_placed() => {
+                let bytes = self.bytes.entry(category.clone()).or_insert(0);
+                let size = action.size.unwrap_or(0);
+                *bytes = if undo { bytes.saturating_sub(size) } else { *bytes + size };
+                Some(category.as_str())
+            }
+            _ => None,
+        };
+        if let Some(bucket) = bucket {
+            step(self.counts.entry(bucket.to_string()).or_insert(0));
+        }
+        if let Some(conflict) = action.conflict {
+            let bucket = match conflict {
+                Conflict::Renamed => "Renamed",
+                Conflict::Overwritten => "Overwritten",
+            };
+            step(self.counts.entry(bucket.to_string()).or_insert(0));
+        }
+        if action.warning.is_some() {
+            step(self.counts.entry("Errors".to_string()).or_insert(0));
+        }
+        let handled = action.status.is_placed() || matches!(action.status, ActionStatus::Duplicate(_));
+        if handled && action.size == Some(0) {
+            step(self.counts.entry("Empty files".to_string()).or_insert(0));
+        }
+    }
+
+    /// Adds the counts and actions of `other`, e.g. another folder organized
+    /// with the same options, so both can be reported as one run.
+    pub fn merge(&mut self, other: Summary) {
+        for (key, count) in other.counts {
+            *self.counts.entry(key).or_insert(0) += count;
+        }
+        for (key, bytes) in other.bytes {
+            *self.bytes.entry(key).or_insert(0) += bytes;
+        }
+        self.actions.extend(other.actions);
+        self.stopped |= other.stopped;
+        self.removed_dirs.extend(other.removed_dirs);
+        self.elapsed += other.elapsed;
+        self.limit_used += other.limit_used;
+    }
+
+    /// Count for a single bucket (0 if unknown).
+    pub fn count(&self, key: &str) -> usize {
+        self.counts.get(key).copied().unwrap_or_default()
+    }
+
+    /// Bytes placed into a single category (0 if unknown).
+    pub fn bytes(&self, key: &str) -> u64 {
+        self.bytes.get(key).copied().unwrap_or_default()
+    }
+
+    /// Bytes that were (or would be) placed into all categories together.
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes.values().sum()
+    }
+
+    /// Failed entries per kind of failure, most common first. Warnings are
+    /// counted under "Errors" too but aren't included here.
+    pub fn failures_by_kind(&self) -> Vec<(FailureKind, usize)> {
+        let mut by_kind: HashMap<FailureKind, usize> = HashMap::new();
+        for action in &self.actions {
+            if let ActionStatus::Failed(failure) = &action.status {
+                *by_kind.entry(failure.kind).or_insert(0) += 1;
+            }
+        }
+        let mut by_kind: Vec<_> = by_kind.into_iter().collect();
+        by_kind.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        by_kind
+    }
+
+    /// Number of files that were (or would be) placed into a category.
+    pub fn total_files(&self) -> usize {
+        self.counts.iter().filter(|(k, _)| !STATUS_BUCKETS.contains(&k.as_str())).map(|(_, v)| v).sum()
+    }
+
+    /// Buckets in display order: built-in categories, then custom ones
+    /// alphabetically, then "Others" and the status buckets that are in use
+    /// ("Renamed", "Overwritten", "Kept newer", "Unmatched", "Already organized", "Skipped", "Dirs skipped",
+    /// "Symlinks skipped", "Empty files", "Unchanged", "Rolled back", "Duplicates", "Errors").
+    pub fn rows(&self) -> Vec<(&str, usize)> {
+        display_order(&self.counts).into_iter().map(|k| (k, self.count(k))).collect()
+    }
+}
+
+/// The keys of `counts` in the order the summary lists them: built-in
+/// categories, custom ones alphabetically, "Others", then the status buckets.
+pub(crate) fn display_order<V>(counts: &HashMap<String, V>) -> Vec<&str> {
+    let mut custom: Vec<&str> = counts
+        .keys()
+        .map(|k| k.as_str())
+        .filter(|k| !builtin_categories().any(|b| b == *k) && *k != "Others" && !STATUS_BUCKETS.contains(k))
+        .collect();
+    custom.sort();
+    let builtin: Vec<&str> = builtin_categories().filter(|k| counts.contains_key(*k)).collect();
+    builtin
+        .into_iter()
+        .chain(custom)
+        .chain(["Others"])
+        .chain(STATUS_BUCKETS.into_iter().filter(|k| counts.contains_key(*k)))
+        .collect()
+}
+
+/// Answer from an [`organize_with`] confirmation callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Go ahead with this file.
+    Proceed,
+    /// Leave this file alone and continue with the next one.
+    Skip,
+    /// Leave this file alone and end the run (the partial summary is still returned).
+    Stop,
+}
+
+/// A file that is about to be copied or moved, passed to the confirmation
+/// callback of [`organize_with`].
+#[derive(Debug, Clone, Copy)]
+pub struct PlannedAction<'a> {
+    pub source: &'a Path,
+    /// Destination folder the file will be placed in.
+    pub dest_dir: &'a Path,
+    pub category: &'a str,
+}
+
+/// Organizes the folder at `path` according to `options` and returns the
+/// resulting counts and actions. Fails only if the folder itself can't be read; problems
+/// with individual files are counted under "Errors".
+pub fn organize(path: &Path, options: &Options) -> io::Result<Summary> {
+    organize_with(path, options, |_| Decision::Proceed)
+}
+
+/// Like [`organize`], but asks `confirm` before each file is copied or moved.
+/// The callback is not consulted in dry runs, since nothing is touched. With
+/// `jobs > 1` it may be called from worker threads, one call at a time.
+pub fn organize_with<F>(path: &Path, options: &Options, confirm: F) -> io::Result<Summary>
+where
+    F: FnMut(&PlannedAction) -> Decision + Send,
+{
+    organize_with_progress(path, options, confirm, |_, _| {})
+}
+
+/// Like [`organize_with`], but also calls `progress` after each file with the
+/// number of files handled so far and the file just handled. Together with
+/// [`count_files`] this is enough to drive a progress bar.
+pub fn organize_with_progress<F, P>(path: &Path, options: &Options, confirm: F, progress: P) -> io::Result<Summary>
+where
+    F: FnMut(&PlannedAction) -> Decision + Send,
+    P: FnMut(usize, &Path) + Send,
+{
+    organize_with_callbacks(path, options, confirm, progress, |_| {})
+}
+
+/// Like [`organize_with_progress`], but also hands every [`FileAction`] to
+/// `on_action` as soon as it is known, in the order of [`Summary::actions`],
+/// so a program can keep its own log while the run goes on. The summary
+/// returned is still the final word: `transactional` may roll back moves
+/// that were already reported.
+pub fn organize_with_callbacks<F, P, A>(
+    path: &Path,
+    options: &Options,
+    mut confirm: F,
+    mut progress: P,
+    mut on_action: A,
+) -> io::Result<Summary>
+where
+    F: FnMut(&PlannedAction) -> Decision + Send,
+    P: FnMut(usize, &Path) + Send,
+    A: FnMut(&FileAction) + Send,
+{
+    let root = canonical_dir(path)?;
+    let out_root = match &options.output {
+        Some(output) => run::resolve_output_root(output, options.dry_run)?,
+        None => root.clone(),
+    };
+
+    let log = match &options.log {
+        Some(log) if !options.dry_run => Some(oplog::OperationLog::open(log).map_err(|e| {
+            io::Error::new(e.kind(), format!("cannot open log file '{}': {}", log.display(), e))
+        })?),
+        _ => None,
+    };
+
+    run::Run::new(options, &out_root, &mut confirm, log)
+        .with_progress(&mut progress)
+        .with_action_callback(&mut on_action)
+        .execute(&root)
+}
+
+/// Like [`organize_with_progress`], but sorts exactly the given `files`
+/// (e.g. from `find`) instead of the contents of a folder. They go into
+/// `options.output`, which is required here. Fails only if the output folder
+/// can't be used; listed paths that aren't existing regular files are counted
+/// under "Errors".
+pub fn organize_files_with_progress<F, P>(
+    files: Vec<PathBuf>,
+    options: &Options,
+    confirm: F,
+    progress: P,
+) -> io::Result<Summary>
+where
+    F: FnMut(&PlannedAction) -> Decision + Send,
+    P: FnMut(usize, &Path) + Send,
+{
+    organize_files_with_callbacks(files, options, confirm, progress, |_| {})
+}
+
+/// Like [`organize_files_with_progress`], with the `on_action` callback of
+/// [`organize_with_callbacks`].
+pub fn organize_files_with_callbacks<F, P, A>(
+    files: Vec<PathBuf>,
+    options: &Options,
+    mut confirm: F,
+    mut progress: P,
+    mut on_action: A,
+) -> io::Result<Summary>
+where
+    F: FnMut(&PlannedAction) -> Decision + Send,
+    P: FnMut(usize, &Path) + Send,
+    A: FnMut(&FileAction) + Send,
+{
+    let Some(output) = &options.output else {
+        let message = "an output folder is needed to organize a list of files";
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    };
+    let out_root = run::resolve_output_root(output, options.dry_run)?;
+
+    let log = match &options.log {
+        Some(log) if !options.dry_run => Some(oplog::OperationLog::open(log).map_err(|e| {
+            io::Error::new(e.kind(), format!("cannot open log file '{}': {}", log.display(), e))
+        })?),
+        _ => None,
+    };
+
+    Ok(run::Run::new(options, &out_root, &mut confirm, log)
+        .with_progress(&mut progress)
+        .with_action_callback(&mut on_action)
+        .execute_files(files))
+}
+
+/// Moves the files an earlier [`organize`] run over `path` moved back to
+/// where they came from, the way `transactional` does when that run itself
+/// fails. For undoing the folders before the one that failed when several
+/// are organized in turn. Returns `summary` recounted to match.
+pub fn roll_back(path: &Path, options: &Options, summary: Summary) -> io::Result<Summary> {
+    let root = canonical_dir(path)?;
+    let out_root = match &options.output {
+        Some(output) => run::resolve_output_root(output, true)?,
+        None => root,
+    };
+    Ok(run::roll_back(summary, &out_root))
+}
+
+/// Splits a list of paths separated by `separator` (`b'\n'`, or `0` for
+/// `find -print0`), skipping empty entries. With newlines a trailing `\r` is
+/// dropped too; everything else, spaces included, is part of the path.
+pub fn parse_path_list(data: &[u8], separator: u8) -> Vec<PathBuf> {
+    data.split(|b| *b == separator)
+        .map(|entry| if separator == b'\n' { entry.strip_suffix(b"\r").unwrap_or(entry) } else { entry })
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Number of files an [`organize`] run over `path` would look at, i.e. the
+/// total the `progress` callback of [`organize_with_progress`] counts up to
+/// (unless the run is stopped early). Touches nothing on disk.
+pub fn count_files(path: &Path, options: &Options) -> io::Result<usize> {
+    let root = canonical_dir(path)?;
+    let out_root = match &options.output {
+        Some(output) => run::resolve_output_root(output, true)?,
+        None => root.clone(),
+    };
+    run::Run::new(options, &out_root, &mut |_| Decision::Proceed, None).count_files(&root)
+}
+
+/// Tallies how many files of each category `path` holds and how big they
+/// are, plus the `top` largest files, without organizing anything. Files
+/// are picked and classified exactly as an [`organize`] run would.
+pub fn folder_stats(path: &Path, options: &Options, top: usize) -> io::Result<stats::FolderStats> {
+    let root = canonical_dir(path)?;
+    let out_root = match &options.output {
+        Some(output) => run::resolve_output_root(output, true)?,
+        None => root.clone(),
+    };
+    run::Run::new(options, &out_root, &mut |_| Decision::Proceed, None).stats(&root, top)
+}
+
+/// Whether `path` is one of the folders an [`organize`] run into `out_root`
+/// sorts files into: a category's folder (custom categories included, under
+/// the name `folder_names` gives it) or a subcategory's folder inside one.
+/// With `by_owner` the category folders sit in the owners' folders instead.
+/// Recursive runs don't descend into these, so they never re-sort their own
+/// output. Only names and places are compared; nothing has to exist.
+pub fn is_category_dir(path: &Path, out_root: &Path, options: &Options) -> bool {
+    if options.by_date && options.date_only {
+        return false;
+    }
+    let name = |dir: &Path| dir.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
+    let category_of = |dir: &Path| {
+        let parent = dir.parent()?;
+        if parent != out_root && !(options.by_owner && parent.parent() == Some(out_root)) {
+            return None;
+        }
+        let name = name(dir);
+        let hidden = (options.hidden == HiddenFiles::Separate).then_some(HIDDEN_CATEGORY);
+        let small = options.small_files.map(|_| SMALL_FILES_CATEGORY);
+        let mut categories = options.categories.keys().map(String::as_str).chain(["Others"]).chain(hidden).chain(small);
+        categories.find(|category| options.folder_name(category) == name)
+    };
+    if category_of(path).is_some() {
+        return true;
+    }
+    let subcategories = path.parent().and_then(category_of).and_then(|category| options.subcategories.get(category));
+    subcategories.is_some_and(|subs| subs.contains_key(&name(path)))
+}
+
+/// Categories whose folder can't be created by an [`organize`] run over
+/// `path` because a file (not a folder) already has its name there, each
+/// with that file. Every file of such a category would fail to be placed.
+pub fn blocked_folders(path: &Path, options: &Options) -> io::Result<Vec<(String, PathBuf)>> {
+    let root = canonical_dir(path)?;
+    let out_root = match &options.output {
+        Some(output) => run::resolve_output_root(output, true)?,
+        None => root,
+    };
+    if options.by_date && options.date_only {
+        return Ok(Vec::new());
+    }
+    let mut categories: Vec<&str> = options.categories.keys().map(String::as_str).chain(["Others"]).collect();
+    if options.hidden == HiddenFiles::Separate {
+        categories.push(HIDDEN_CATEGORY);
+    }
+    if options.small_files.is_some() {
+        categories.push(SMALL_FILES_CATEGORY);
+    }
+    let mut blocked: Vec<(String, PathBuf)> = categories
+        .into_iter()
+        .filter_map(|category| {
+            let folder = match options.destinations.get(category) {
+                Some(dest) => out_root.join(dest),
+                None => out_root.join(options.folder_name(category)),
+            };
+            let is_file = fs::metadata(&folder).is_ok_and(|meta| !meta.is_dir());
+            is_file.then(|| (category.to_string(), folder))
+        })
+        .collect();
+    blocked.sort();
+    Ok(blocked)
+}
+
+/// A folder name to use instead of the file `blocked` that nothing has yet:
+/// `Images_files`, then `Images_files_2` and so on.
+pub fn free_folder_name(blocked: &Path) -> String {
+    let name = blocked.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let parent = blocked.parent().unwrap_or(Path::new(""));
+    (1..)
+        .map(|n| if n == 1 { format!("{}_files", name) } else { format!("{}_files_{}", name, n) })
+        .find(|candidate| !parent.join(candidate).exists())
+        .expect("some name is free")
+}
+
+fn canonical_dir(path: &Path) -> io::Result<PathBuf> {
+    let root = fs::canonicalize(path)?;
+    if !root.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotADirectory, format!("'{}' is not a directory", root.display())));
+    }
+    Ok(root)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_lists_keep_spaces_and_drop_empty_entries() {
+        let lines = parse_path_list(b"./my doc.pdf\r\n\n./a.txt\n", b'\n');
+        assert_eq!(lines, [PathBuf::from("./my doc.pdf"), PathBuf::from("./a.txt")]);
+        let nul = parse_path_list(b"a\nb.txt\0c.txt\0", 0);
+        assert_eq!(nul, [PathBuf::from("a\nb.txt"), PathBuf::from("c.txt")]);
+    }
+
+    #[test]
+    fn every_category_gets_a_summary_row() {
+        let mut categories = build_category_map();
+        categories.insert("Zines".to_string(), vec!["zine".to_string()]);
+        categories.insert("Models".to_string(), vec!["stl".to_string()]);
+        let options = Options { categories: categories.clone(), ..Options::default() };
+        let summary = Summary::new(&options);
+        let rows: Vec<&str> = summary.rows().into_iter().map(|(k, _)| k).collect();
+
+        let expected: Vec<&str> = builtin_categories().chain(["Models", "Zines", "Others", "Errors"]).collect();
+        assert_eq!(rows, expected);
+        assert!(categories.keys().all(|k| rows.contains(&k.as_str())));
+    }
+
+    #[test]
+    fn no_create_empty_category_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("photo.jpg"), b"jpg").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"txt").unwrap();
+        let options = Options { categories: build_category_map(), ..Options::default() };
+        // The photo disappears before it's copied, so nothing lands in Images
+        let summary = organize_with(dir.path(), &options, |planned| {
+            if planned.category == "Images" {
+                std::fs::remove_file(planned.source).unwrap();
+            }
+            Decision::Proceed
+        })
+        .unwrap();
+
+        assert_eq!(summary.count("Errors"), 1);
+        assert!(dir.path().join("Documents/notes.txt").is_file());
+        assert!(!dir.path().join("Images").exists());
+    }
+
+    #[test]
+    fn moves_pass_over_a_name_taken_after_it_was_picked() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("photo.jpg"), b"mine").unwrap();
+        let options = Options { categories: build_category_map(), move_files: true, ..Options::default() };
+        // Another program saves a photo of the same name just before the move
+        let summary = organize_with(dir.path(), &options, |planned| {
+            std::fs::create_dir_all(planned.dest_dir).unwrap();
+            std::fs::write(planned.dest_dir.join("photo.jpg"), b"theirs").unwrap();
+            Decision::Proceed
+        })
+        .unwrap();
+
+        assert_eq!(summary.count("Images"), 1);
+        assert_eq!(std::fs::read(dir.path().join("Images/photo.jpg")).unwrap(), b"theirs");
+        assert_eq!(std::fs::read(dir.path().join("Images/photo_1.jpg")).unwrap(), b"mine");
+        assert!(!dir.path().join("photo.jpg").exists());
+    }
+
+    #[test]
+    fn actions_that_arent_kept_are_still_counted_and_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.jpg"), b"a").unwrap();
+        std::fs::write(dir.path().join("b.txt"), b"bb").unwrap();
+        let options = Options { dry_run: true, keep_actions: false, ..Options::default() };
+        let mut reported = Vec::new();
+        let summary = organize_with_callbacks(
+            dir.path(),
+            &options,
+            |_| Decision::Proceed,
+            |_, _| {},
+            |action| reported.push(action.source.clone()),
+        )
+        .unwrap();
+
+        assert!(summary.actions.is_empty());
+        assert_eq!((summary.count("Images"), summary.count("Documents"), summary.total_bytes()), (1, 1, 3));
+        reported.sort();
+        assert_eq!(reported, [dir.path().join("a.jpg"), dir.path().join("b.txt")]);
+    }
+
+    #[test]
+    fn transactional_runs_keep_the_counts_of_actions_they_dont_keep() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.jpg"), b"a").unwrap();
+        std::fs::write(dir.path().join("b.jpg"), b"bb").unwrap();
+        std::fs::write(dir.path().join("c.weird"), b"c").unwrap();
+        let options = Options {
+            move_files: true,
+            transactional: true,
+            dry_run: true,
+            keep_actions: false,
+            others: OthersPolicy::Error,
+            output: Some(dir.path().join("out")),
+            ..Options::default()
+        };
+        // Listed, so the failure comes last
+        let files = || ["a.jpg", "b.jpg", "c.weird"].map(|name| dir.path().join(name)).to_vec();
+        let run = |options: &Options| {
+            organize_files_with_progress(files(), options, |_| Decision::Proceed, |_, _| {}).unwrap()
+        };
+        let summary = run(&options);
+        assert_eq!((summary.count("Images"), summary.count("Errors"), summary.total_bytes()), (2, 1, 3));
+        assert_eq!(summary.count("Rolled back"), 0);
+
+        // For real, the moves are kept, put back and recounted
+        let summary = run(&Options { dry_run: false, ..options });
+        assert_eq!((summary.count("Images"), summary.count("Rolled back"), summary.total_bytes()), (0, 2, 0));
+        assert_eq!(summary.count("Errors"), 1);
+        assert!(dir.path().join("a.jpg").exists() && dir.path().join("b.jpg").exists());
+    }
+
+    #[test]
+    fn failures_use_up_the_limit_once_across_folders() {
+        let first = tempfile::tempdir().unwrap();
+        std::fs::write(first.path().join("a.jpg"), b"a").unwrap();
+        // A file where the Images folder should go, so a.jpg fails
+        std::fs::write(first.path().join("Images"), b"in the way").unwrap();
+        let options = Options { limit: Some(3), ..Options::default() };
+        let summary = organize(first.path(), &options).unwrap();
+        assert_eq!((summary.total_files(), summary.count("Errors"), summary.limit_used), (1, 1, 2));
+
+        // The next folder gets what's left, the way the command line passes it on
+        let second = tempfile::tempdir().unwrap();
+        std::fs::write(second.path().join("b.txt"), b"b").unwrap();
+        std::fs::write(second.path().join("c.txt"), b"c").unwrap();
+        let options = Options { limit: Some(3 - summary.limit_used), ..options };
+        let summary = organize(second.path(), &options).unwrap();
+        assert_eq!((summary.total_files(), summary.limit_used), (1, 1));
+        assert!(summary.stopped);
+    }
+
+    #[test]
+    fn empty_files_are_counted_and_can_be_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("failed.pdf"), b"").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"txt").unwrap();
+        let options = Options { categories: build_category_map(), dry_run: true, ..Options::default() };
+        let summary = organize(dir.path(), &options).unwrap();
+        assert_eq!((summary.count("Documents"), summary.count("Empty files")), (2, 1));
+
+        let options = Options { skip_empty: true, ..options };
+        let summary = organize(dir.path(), &options).unwrap();
+        assert_eq!((summary.count("Documents"), summary.count("Empty files")), (1, 1));
+        assert_eq!(summary.total_files(), 1);
+    }
+
+    #[test]
+    fn newest_conflict_mode_keeps_the_later_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("Documents");
+        std::fs::create_dir(&docs).unwrap();
+        let hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        for (name, older) in [("newer.txt", "existing"), ("older.txt", "new")] {
+            std::fs::write(dir.path().join(name), b"new").unwrap();
+            std::fs::write(docs.join(name), b"existing").unwrap();
+            let older = if older == "new" { dir.path().join(name) } else { docs.join(name) };
+            std::fs::File::options().write(true).open(older).unwrap().set_modified(hour_ago).unwrap();
+        }
+        let options =
+            Options { categories: build_category_map(), on_conflict: ConflictPolicy::Newest, ..Options::default() };
+        let summary = organize(dir.path(), &options).unwrap();
+        assert_eq!((summary.count("Overwritten"), summary.count("Kept newer")), (1, 1));
+        assert_eq!(std::fs::read(docs.join("newer.txt")).unwrap(), b"new");
+        assert_eq!(std::fs::read(docs.join("older.txt")).unwrap(), b"existing");
+    }
+
+    #[test]
+    fn file_named_like_a_category_blocks_its_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Images"), b"not a folder").unwrap();
+        std::fs::write(dir.path().join("photo.jpg"), b"jpg").unwrap();
+        let options = Options { categories: build_category_map(), ..Options::default() };
+
+        let blocked = blocked_folders(dir.path(), &options).unwrap();
+        let images = std::fs::canonicalize(dir.path()).unwrap().join("Images");
+        assert_eq!(blocked, [("Images".to_string(), images.clone())]);
+        assert_eq!(free_folder_name(&images), "Images_files");
+
+        // Without the check up front, the failure says what's in the way
+        let summary = organize(dir.path(), &options).unwrap();
+        let failed = summary.actions.iter().find_map(|a| match &a.status {
+            ActionStatus::Failed(failure) => Some(failure.message.clone()),
+            _ => None,
+        });
+        assert!(failed.unwrap().contains("is a file"));
+
+        let mut options = options;
+        options.folder_names.insert("Images".to_string(), free_folder_name(&images));
+        assert!(blocked_folders(dir.path(), &options).unwrap().is_empty());
+        organize(dir.path(), &options).unwrap();
+        assert!(dir.path().join("Images_files/photo.jpg").is_file());
+    }
+
+    #[test]
+    fn category_dirs_are_recognized_by_name_and_place() {
+        let mut categories = build_category_map();
+        categories.insert("Zines".to_string(), vec!["zine".to_string()]);
+        let folder_names = HashMap::from([("Images".to_string(), "Bilder".to_string())]);
+        let options = Options { categories, folder_names, ..Options::default() };
+        let out = Path::new("/out");
+        assert!(is_category_dir(&out.join("Bilder"), out, &options));
+        assert!(is_category_dir(&out.join("Zines"), out, &options));
+        assert!(is_category_dir(&out.join("Others"), out, &options));
+        assert!(is_category_dir(&out.join("Code/Rust"), out, &options));
+        assert!(!is_category_dir(&out.join("Images"), out, &options));
+        assert!(!is_category_dir(&out.join("Rust"), out, &options));
+        assert!(!is_category_dir(&out.join("project/Zines"), out, &options));
+        assert!(!is_category_dir(&out.join("Documents/Rust"), out, &options));
+
+        let options = Options { by_owner: true, ..options };
+        assert!(is_category_dir(&out.join("alice/Zines"), out, &options));
+    }
+
+    #[test]
+    fn recursive_runs_leave_their_own_output_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("trip")).unwrap();
+        std::fs::write(dir.path().join("trip/photo.jpg"), b"jpg").unwrap();
+        std::fs::write(dir.path().join("main.rs"), b"fn main() {}").unwrap();
+        let folder_names = HashMap::from([("Images".to_string(), "Bilder".to_string())]);
+        let options = Options {
+            categories: build_category_map(),
+            folder_names,
+            recursive: true,
+            subcategorize: true,
+            move_files: true,
+            ..Options::default()
+        };
+        assert_eq!(organize(dir.path(), &options).unwrap().total_files(), 2);
+        assert!(dir.path().join("Bilder/photo.jpg").is_file());
+        assert!(dir.path().join("Code/Rust/main.rs").is_file());
+        assert_eq!(organize(dir.path(), &options).unwrap().total_files(), 0);
+    }
+
+    #[test]
+    fn every_action_reaches_the_callback_as_it_happens() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("photo.jpg"), b"jpg").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"txt").unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let options = Options { categories: build_category_map(), jobs: 2, ..Options::default() };
+
+        let mut seen = Vec::new();
+        let summary =
+            organize_with_callbacks(dir.path(), &options, |_| Decision::Proceed, |_, _| {}, |a| seen.push(a.clone()))
+                .unwrap();
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen, summary.actions);
+    }
+}
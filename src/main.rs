@@ -1,186 +1,1958 @@
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::fs;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
-
-/// Returns lowercase extension string for a path, e.g. "jpg" or "" if none.
-fn file_extension_lowercase(path: &Path) -> String {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .map(|s| s.to_lowercase())
-        .unwrap_or_else(|| "".to_string())
-}
-
-/// Build a mapping of category -> Vec<extensions>
-fn build_category_map() -> HashMap<&'static str, Vec<&'static str>> {
-    let mut m = HashMap::new();
-    m.insert("Images", vec!["jpg", "jpeg", "png", "gif", "svg", "bmp", "webp"]);
-    m.insert("Documents", vec!["pdf", "doc", "docx", "txt", "xls", "xlsx", "ppt", "pptx"]);
-    m.insert("Videos", vec!["mp4", "mov", "mkv", "webm", "avi"]);
-    m.insert("Audio", vec!["mp3", "wav", "flac", "aac"]);
-    m.insert("Archives", vec!["zip", "rar", "tar", "gz", "7z"]);
-    m.insert("Code", vec!["rs", "py", "js", "ts", "go", "java", "c", "cpp", "html", "css", "json", "yaml", "yml"]);
-    m
-}
-
-/// Given an extension, find category name, or "Others"
-fn category_for_extension<'a>(ext: &str, categories: &'a HashMap<&str, Vec<&str>>) -> &'a str {
-    for (cat, exts) in categories {
-        if exts.iter().any(|e| e == &ext) {
-            return cat;
-        }
-    }
-    "Others"
-}
-
-fn copy_file_to_category(src: &Path, dest_dir: &Path) -> io::Result<PathBuf> {
-    // Ensure destination directory exists
-    fs::create_dir_all(dest_dir)?;
-    // Build destination file path
-    let file_name = src.file_name().expect("file should have a name");
-    let mut dest_path = dest_dir.join(file_name);
-
-    // If a file with the same name already exists in destination, append a counter
-    if dest_path.exists() {
-        let mut count = 1;
-        let stem = src.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
-        let ext = src.extension().and_then(|e| e.to_str()).map(|s| format!(".{}", s)).unwrap_or_else(|| "".to_string());
-        loop {
-            let new_name = format!("{}_{}{}", stem, count, ext);
-            dest_path = dest_dir.join(new_name);
-            if !dest_path.exists() {
-                break;
-            }
-            count += 1;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+mod completions;
+mod output;
+
+use file_organizer::dates::{Bound, DateField};
+use file_organizer::exec;
+use file_organizer::ignore;
+use file_organizer::oplog::OperationLog;
+use file_organizer::plan::{self, ApplyStatus, Plan, PlanWriter};
+use file_organizer::rename;
+use file_organizer::rules;
+use file_organizer::script;
+use file_organizer::space;
+use file_organizer::state;
+use file_organizer::tag::TagPattern;
+use file_organizer::undo::{self, UndoStatus};
+use output::{ColorChoice, say, say_err};
+
+use file_organizer::{
+    blocked_folders, build_category_map, build_subcategory_map, config, count_files, dates, file_extension,
+    folder_stats, free_folder_name, is_hidden, organize_files_with_callbacks, organize_with_callbacks, parse_path_list,
+    report, roll_back, size, ActionStatus, Change, Conflict, ConflictFormat, ConflictPolicy, Decision,
+    DEFAULT_SMALL_THRESHOLD, EmptyDirs, FailureKind, FileAction, HiddenFiles, HIDDEN_CATEGORY, LinkKind, Options,
+    OthersPolicy, PlannedAction, SMALL_FILES_CATEGORY, Summary,
+};
+
+/// Fetches the value following a flag like `--max-depth`, exiting with a clear
+/// message if it is missing.
+fn flag_value<'a>(flag: &str, rest: &mut impl Iterator<Item = &'a String>) -> &'a String {
+    match rest.next() {
+        Some(value) => value,
+        None => {
+            say_err!("❌ Error: {} expects a value", flag);
+            std::process::exit(1);
         }
     }
+}
 
-    fs::copy(src, &dest_path)?;
-    Ok(dest_path)
+/// Parses a glob for `--include` / `--exclude`, exiting on a malformed pattern.
+fn glob_pattern(flag: &str, value: &str) -> glob::Pattern {
+    match glob::Pattern::new(value) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            say_err!("❌ Error: invalid {} pattern '{}': {}", flag, value, e);
+            std::process::exit(1);
+        }
+    }
 }
 
-fn print_usage_and_exit(program: &str) {
+/// Parses a size for `--min-size` / `--max-size`, exiting on a malformed value.
+fn size_value(flag: &str, value: &str) -> u64 {
+    match size::parse_size(value) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            say_err!("❌ Error: {}: {}", flag, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parses a date for `--since` / `--until`, exiting on a malformed value.
+fn date_value(flag: &str, value: &str, bound: Bound) -> SystemTime {
+    match dates::parse_date_bound(value, bound, SystemTime::now()) {
+        Ok(time) => time,
+        Err(e) => {
+            say_err!("❌ Error: {}: {}", flag, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Exit code when the run went through but some files had errors (the
+/// "Errors" count). Bad arguments and folders that can't be read exit with 1.
+const EXIT_FILE_ERRORS: i32 = 2;
+
+/// Command name the completion scripts are registered for.
+const BIN_NAME: &str = env!("CARGO_BIN_NAME");
+
+/// Folder organized when no folder argument is given.
+const DEFAULT_DIR_VAR: &str = "FILE_ORGANIZER_DEFAULT_DIR";
+
+fn print_usage(program: &str) {
     println!("Usage:");
-    println!("  {} <folder-path> [--dry-run]", program);
+    println!("  {} <folder-path>... [--dry-run] [--move] [--recursive] [--max-depth N]", program);
+    println!("      [--config FILE | --no-config] [--profile NAME] [--merge-config] [--map EXTS=CATEGORY]...");
+    println!("      [--priority CATEGORIES] [--rules FILE] [--json] [--summary-json] [--output DIR] [--interactive]");
+    println!("      [--dedup]");
+    println!("      [--by-date] [--date-only] [--date-format FMT] [--date-field created|modified|accessed]");
+    println!("      [--by-exif-date] [--by-owner] [--quiet] [--verbose] [--timing]");
+    println!("      [--log FILE] [--exec CMD | --exec-shell CMD] [--include GLOB]... [--exclude GLOB]...");
+    println!("      [--skip-empty] [--min-size SIZE] [--max-size SIZE] [--since DATE] [--until DATE]");
+    println!("      [--since-last-run] [--jobs N] [--no-progress] [--preserve-times | --preserve times|all]");
+    println!("      [--verify] [--retries N] [--link hard|sym]");
+    println!("      [--case-sensitive] [--sniff-content] [--by-mime] [--on-conflict rename|skip|overwrite|newest]");
+    println!("      [--trash-on-overwrite] [--conflict-format TEMPLATE] [--rename RULES] [--normalize-unicode]");
+    println!("      [--follow-symlinks] [--others folder|leave|error] [--group-small-files] [--small-threshold SIZE]");
+    println!("      [--skip-hidden | --hidden-category] [--subcategorize] [--by-prefix] [--prefix-min N]");
+    println!("      [--tag-pattern REGEX] [--extract-archives [--extract-nested]]");
+    println!("      [--fail-fast] [--transactional] [--limit N] [--force] [--yes] [--color auto|always|never]");
+    println!("      [--flatten] [--flatten-remove-empty] [--prune-empty-dirs | --prune-all-empty] [--keep-structure]");
+    println!("      [--summary-out FILE] [--csv FILE] [--no-ignore] [--rename-blocked-folders]");
+    println!("  find ... | {} --stdin [--null] --output DIR [options]", program);
+    println!("  {} <folder-path> --stats-only [--top N] [--json] [filter options]", program);
+    println!("  {} <folder-path> --extension-report [--json] [filter options]", program);
+    println!("  {} <folder-path> --report-only-others [--json] [filter options]", program);
+    println!("  {} <folder-path> --dry-run --tree [options]", program);
+    println!("  {} <folder-path> --emit-sh [options] > organize.sh   (implies --dry-run)", program);
+    println!("  {} <folder-path> --diff [options]   (implies --dry-run)", program);
+    println!("  {} <folder-path> --plan PLANFILE [options]   (implies --dry-run)", program);
+    println!("  {} --apply PLANFILE [--dry-run] [--log FILE]", program);
+    println!("  {} --undo LOGFILE [--dry-run]", program);
+    println!("  {} --list-profiles [--config FILE]", program);
+    println!("  {} --help | --version", program);
+    println!();
+    println!("Without <folder-path>, the folder in ${} is organized.", DEFAULT_DIR_VAR);
     println!();
     println!("Examples:");
     println!("  cargo run -- /mnt/c/Users/DELL/Downloads");
     println!("  cargo run -- /mnt/c/Users/DELL/Downloads --dry-run");
+    println!("  cargo run -- /mnt/c/Users/DELL/Downloads --move");
+    println!("  cargo run -- /mnt/c/Users/DELL/Downloads --recursive --max-depth 2");
+    println!("  cargo run -- /mnt/c/Users/DELL/Downloads --config categories.toml --merge-config");
+    println!("  cargo run -- /mnt/c/Users/DELL/Downloads --dry-run --json");
+    println!("  cargo run -- /mnt/c/Users/DELL/Downloads --output /mnt/c/Users/DELL/Sorted");
+    println!("  cargo run -- /mnt/c/Users/DELL/Pictures --by-date --date-format %Y/%m-%d");
+    println!("  cargo run -- /mnt/c/Users/DELL/Pictures --profile photos");
+}
+
+/// The usage message after a bad invocation, which is an error.
+fn print_usage_and_exit(program: &str) -> ! {
+    print_usage(program);
+    println!();
+    println!("Run '{} --help' to see every option.", program);
     std::process::exit(1);
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let program = args.get(0).map(|s| s.as_str()).unwrap_or("file_organizer");
+/// `--help`: the usage message and every option with what it does. Asked
+/// for, so it isn't an error.
+fn print_help_and_exit(program: &str) -> ! {
+    print_usage(program);
+    println!();
+    println!("Options:");
+    print!("{}", completions::options_help());
+    std::process::exit(0);
+}
+
+/// How much per-file output to print. Errors always go to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    /// No per-file lines and no headers, only the summary.
+    Quiet,
+    Normal,
+    /// Also explain how each file was classified and list skipped entries.
+    Verbose,
+}
+
+/// Prints the emoji log line for a single action. Failures and warnings are
+/// left to [`print_problems`].
+fn print_action(action: &FileAction, opts: &Options, verbosity: Verbosity) {
+    let verb = opts.verb();
+    let src = action.source.display();
+    let dest = action.destination.as_deref().unwrap_or(Path::new(""));
+    match &action.status {
+        ActionStatus::Failed(_) => {}
+        _ if verbosity == Verbosity::Quiet => {}
+        // --diff only lists what's new or changed
+        _ if action.change == Some(Change::Duplicate) => {
+            if verbosity == Verbosity::Verbose {
+                say!("♻️ Already there: '{}'", src);
+            }
+        }
+        ActionStatus::Skipped(_) if action.change == Some(Change::Conflict) => {
+            say!("❓ Changed: '{}' (a different file of that name is already there, so it would be skipped)", src);
+        }
+        ActionStatus::Copied if opts.link.is_some() => {
+            say!("✅ Linked: '{}' -> '{}'{}", src, dest.display(), conflict_note(action))
+        }
+        ActionStatus::Copied => say!("✅ Copied: '{}' -> '{}'{}", src, dest.display(), conflict_note(action)),
+        ActionStatus::Moved => say!("✅ Moved: '{}' -> '{}'{}", src, dest.display(), conflict_note(action)),
+        ActionStatus::WouldCopy | ActionStatus::WouldMove => {
+            let dir = dest.parent().unwrap_or(dest);
+            match action.change {
+                Some(Change::Conflict) => {
+                    say!("❓ Would {} (changed): '{}' -> '{}'{}", verb, src, dir.display(), conflict_note(action))
+                }
+                Some(_) => {
+                    say!("➡️ Would {} (new): '{}' -> '{}'{}", verb, src, dir.display(), conflict_note(action))
+                }
+                None => say!("➡️ Would {}: '{}' -> '{}'{}", verb, src, dir.display(), conflict_note(action)),
+            }
+        }
+        ActionStatus::Duplicate(original) => {
+            say!("♻️ Duplicate: '{}' (same contents as '{}')", src, original.display())
+        }
+        ActionStatus::Skipped(reason) => {
+            if verbosity == Verbosity::Verbose {
+                say!("⏭️ Skipped: '{}' ({})", src, reason);
+            }
+        }
+    }
+    if let (Verbosity::Verbose, Some(category)) = (verbosity, &action.category) {
+        let reason = match &action.content_type {
+            Some(content_type) => format!("contents look like '{}' -> {}", content_type, opts.folder_name(category)),
+            None => classification_reason(&action.source, category, opts),
+        };
+        say!("   🔍 {}", reason);
+    }
+}
+
+/// Lists every failure and warning of the run together on stderr, so they
+/// don't get lost between the per-file lines.
+fn print_problems(actions: &[FileAction], verb: &str) {
+    let problems: Vec<&FileAction> = actions
+        .iter()
+        .filter(|a| matches!(a.status, ActionStatus::Failed(_)) || a.warning.is_some())
+        .collect();
+    if problems.is_empty() {
+        return;
+    }
+    say_err!("\n❗ Problems ({}):", problems.len());
+    for action in problems {
+        let src = action.source.display();
+        match &action.status {
+            ActionStatus::Failed(e) if action.category.is_some() => {
+                say_err!("❌ Failed to {} '{}': {}", verb, src, e)
+            }
+            ActionStatus::Failed(e) => say_err!("⚠️ '{}': {}", src, e),
+            _ => {}
+        }
+        if let Some(warning) = &action.warning {
+            say_err!("⚠️ '{}': {}", src, warning);
+        }
+    }
+}
+
+/// The per-category counts block of the summary, one line per row. The name
+/// column is as wide as the longest bucket name so custom categories line up.
+fn summary_lines(summary: &Summary, opts: &Options) -> Vec<String> {
+    let rows = summary.rows();
+    let width = rows.iter().map(|(key, _)| opts.folder_name(key).chars().count()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for (key, count) in rows {
+        if summary.bytes.contains_key(key) {
+            let files = if count == 1 { "file" } else { "files" };
+            let bytes = size::format_size(summary.bytes(key));
+            let name = opts.folder_name(key);
+            lines.push(format!("  - {:<width$} : {} {} ({})", name, count, files, bytes));
+        } else if key == "Errors" && count > 0 {
+            let kinds: Vec<String> = summary.failures_by_kind().iter().map(|(k, n)| format!("{} {}", n, k)).collect();
+            let warnings = summary.actions.iter().filter(|a| a.warning.is_some()).count();
+            let mut detail = kinds.join(", ");
+            if warnings > 0 {
+                let warn = format!("{} warning{}", warnings, if warnings == 1 { "" } else { "s" });
+                detail = if detail.is_empty() { warn } else { format!("{}, {}", detail, warn) };
+            }
+            lines.push(format!("  - {:<width$} : {} ({})", key, count, detail));
+        } else {
+            lines.push(format!("  - {:<width$} : {}", key, count));
+        }
+    }
+    let done = if opts.dry_run { format!("would {}", opts.verb()) } else { opts.past_tense().to_string() };
+    let total_bytes = size::format_size(summary.total_bytes());
+    lines.push(format!("  Total {}: {} ({})", done, summary.total_files(), total_bytes));
+    if !summary.removed_dirs.is_empty() {
+        lines.push(format!("  Empty folders removed: {}", summary.removed_dirs.len()));
+    }
+    if opts.diff && opts.dry_run {
+        let count = |change| summary.actions.iter().filter(|a| a.change == Some(change)).count();
+        lines.push(format!(
+            "  Compared with the output: {} new, {} already there, {} changed",
+            count(Change::New),
+            count(Change::Duplicate),
+            count(Change::Conflict)
+        ));
+    }
+    lines
+}
+
+/// A folder of the `--tree` view: files planned directly into it, and its subfolders.
+#[derive(Default)]
+struct TreeNode {
+    files: usize,
+    children: BTreeMap<String, TreeNode>,
+}
+
+impl TreeNode {
+    /// Files in this folder and all its subfolders.
+    fn total(&self) -> usize {
+        self.files + self.children.values().map(TreeNode::total).sum::<usize>()
+    }
+}
+
+/// Lines of the `--tree` view: the folders below `out_root` files would be
+/// placed into, with how many files each would hold (subfolders included).
+fn tree_lines(actions: &[FileAction], out_root: &Path) -> Vec<String> {
+    let mut root = TreeNode::default();
+    for action in actions.iter().filter(|a| a.status.is_placed()) {
+        let Some(dir) = action.destination.as_deref().and_then(Path::parent) else { continue };
+        let mut node = &mut root;
+        match dir.strip_prefix(out_root) {
+            Ok(rel) => {
+                for part in rel.components() {
+                    node = node.children.entry(part.as_os_str().to_string_lossy().into_owned()).or_default();
+                }
+            }
+            // Categories sent somewhere else by the config's destinations
+            Err(_) => node = node.children.entry(dir.display().to_string()).or_default(),
+        }
+        node.files += 1;
+    }
+    let mut lines = Vec::new();
+    push_tree(&root, "", &mut lines);
+    lines
+}
+
+fn push_tree(node: &TreeNode, indent: &str, lines: &mut Vec<String>) {
+    let last = node.children.len().saturating_sub(1);
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let (branch, nested) = if i == last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        let total = child.total();
+        let files = if total == 1 { "file" } else { "files" };
+        lines.push(format!("{}{}{}/ ({} {})", indent, branch, name, total, files));
+        push_tree(child, &format!("{}{}", indent, nested), lines);
+    }
+}
+
+/// Writes the summary block for `--summary-out`, headed by the folders and
+/// time of the run and followed by every error, grouped by kind.
+fn write_summary_file(
+    out: &Path,
+    roots: &[&Path],
+    opts: &Options,
+    summary: &Summary,
+    lines: &[String],
+) -> io::Result<()> {
+    let mode = if opts.dry_run { format!("{} (dry run)", opts.verb()) } else { opts.verb().to_string() };
+    let label = if roots.len() == 1 { "Folder" } else { "Folders" };
+    let roots: Vec<String> = roots.iter().map(|root| root.display().to_string()).collect();
+    let mut text = format!(
+        "File organizer summary\n{}: {}\nTime: {}\nMode: {}\n\n",
+        label,
+        roots.join(", "),
+        chrono::Local::now().to_rfc3339(),
+        mode
+    );
+    for line in lines {
+        text.push_str(line);
+        text.push('\n');
+    }
+    let mut failures: Vec<(FailureKind, &FileAction, &str)> = summary
+        .actions
+        .iter()
+        .filter_map(|a| match &a.status {
+            ActionStatus::Failed(failure) => Some((failure.kind, a, failure.message.as_str())),
+            _ => None,
+        })
+        .collect();
+    // Permission problems first, since those are the ones to go and fix
+    failures.sort_by_key(|(kind, _, _)| *kind);
+    if !failures.is_empty() {
+        text.push_str(&format!("\nErrors ({}):\n", failures.len()));
+        for (kind, action, message) in failures {
+            text.push_str(&format!("  [{}] '{}': {}\n", kind, action.source.display(), message));
+        }
+    }
+    fs::write(out, text)
+}
+
+/// Canonical path of a folder given on the command line, or why it can't be organized.
+fn resolve_folder(folder_path: &str) -> Result<PathBuf, String> {
+    let canonical = fs::canonicalize(folder_path).map_err(|_| format!("'{}' is not a valid directory!", folder_path))?;
+    if !canonical.is_dir() {
+        return Err(format!("'{}' is not a directory!", canonical.display()));
+    }
+    Ok(canonical)
+}
+
+/// Folders that are far too easy to wreck by organizing them (the user's
+/// home, a filesystem root, the system's own folders), with what they are.
+/// Kept short on purpose: it only catches a mistyped or forgotten path.
+fn dangerous_folder(root: &Path) -> Option<&'static str> {
+    let home = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .filter(|home| !home.is_empty())
+        .and_then(|home| fs::canonicalize(home).ok());
+    if home.as_deref() == Some(root) {
+        return Some("your home folder");
+    }
+    if root.parent().is_none() {
+        return Some("the root of a drive");
+    }
+    let is_system = SYSTEM_FOLDERS.iter().any(|dir| fs::canonicalize(dir).is_ok_and(|dir| dir == root));
+    is_system.then_some("a system folder")
+}
+
+/// Folders [`dangerous_folder`] counts as the system's own.
+#[cfg(windows)]
+const SYSTEM_FOLDERS: &[&str] = &[r"C:\Windows", r"C:\Program Files", r"C:\Program Files (x86)", r"C:\Users"];
+#[cfg(target_os = "macos")]
+const SYSTEM_FOLDERS: &[&str] =
+    &["/Applications", "/Library", "/System", "/Users", "/bin", "/etc", "/private", "/sbin", "/usr", "/var"];
+#[cfg(all(unix, not(target_os = "macos")))]
+const SYSTEM_FOLDERS: &[&str] = &[
+    "/bin", "/boot", "/dev", "/etc", "/home", "/lib", "/lib64", "/opt", "/proc", "/root", "/sbin", "/srv", "/sys",
+    "/usr", "/var",
+];
+#[cfg(not(any(unix, windows)))]
+const SYSTEM_FOLDERS: &[&str] = &[];
+
+/// Suffix for a success line when the destination name was already taken.
+fn conflict_note(action: &FileAction) -> String {
+    let name = || {
+        let dest = action.destination.as_deref().unwrap_or(Path::new(""));
+        dest.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+    };
+    match action.conflict {
+        Some(Conflict::Renamed) => format!(" (name taken, saved as '{}')", name()),
+        Some(Conflict::Overwritten) => " (replaced the existing file)".to_string(),
+        None => String::new(),
+    }
+}
+
+/// Explains why a file landed in `category`, for `--verbose`.
+fn classification_reason(path: &Path, category: &str, opts: &Options) -> String {
+    let ext = file_extension(path, opts.case_sensitive);
+    let folder = opts.folder_name(category);
+    if let Some(i) = rules::first_match(&opts.rules, path, &ext) {
+        format!("rule {} of --rules -> {}", i + 1, folder)
+    } else if category == HIDDEN_CATEGORY && is_hidden(path) {
+        format!("hidden file -> {}", folder)
+    } else if category == SMALL_FILES_CATEGORY
+        && let Some(threshold) = opts.small_files
+    {
+        format!("smaller than {} -> {}", size::format_size(threshold), folder)
+    } else if ext.is_empty() {
+        format!("no extension -> {}", folder)
+    } else if category == "Others" {
+        format!("extension '{}' is not in any category -> {}", ext, folder)
+    } else {
+        format!("extension '{}' -> {}", ext, folder)
+    }
+}
+
+/// Prints the banner describing what this run is about to do.
+fn print_header(root: &Path, opts: &Options, listed: Option<usize>) {
+    if let Some(count) = listed {
+        say!("📥 Organizing {} files listed on stdin", count);
+    } else {
+        say!("📁 Organizing folder: {}", root.display());
+    }
+    if let Some(output) = &opts.output {
+        say!("📂 Output folder: {}", output.display());
+    }
+    let mut destinations: Vec<(&String, &PathBuf)> = opts.destinations.iter().collect();
+    destinations.sort();
+    for (category, dest) in destinations {
+        say!("📂 {} go to: {}", category, dest.display());
+    }
+    if opts.dry_run {
+        say!("🔎 Running in DRY-RUN mode (no files will be {}).", opts.past_tense());
+        if opts.diff {
+            say!("🔎 Comparing with the files already organized; only new and changed files are listed.");
+        }
+    } else if opts.move_files {
+        say!("🚚 Move Mode: files will be MOVED into category folders.");
+    } else if let Some(kind) = opts.link {
+        let kind = if kind == LinkKind::Hard { "hard" } else { "symbolic" };
+        say!("🔗 Link Mode: files stay where they are; the category folders get {} links to them.", kind);
+    } else {
+        say!("⚠️ Safe Mode: files will be COPIED (originals left intact).");
+    }
+    if opts.by_date {
+        let layout = if opts.date_only { "" } else { "<category>/" };
+        let field = if opts.by_exif_date {
+            format!("photo-taken (else {})", opts.date_field)
+        } else {
+            opts.date_field.to_string()
+        };
+        say!("📅 Sorting by {} date into {}{}", field, layout, opts.date_format);
+    }
+    if opts.since_last_run && listed.is_none() {
+        let out_root = opts.output.as_deref().map_or(root.to_path_buf(), |o| fs::canonicalize(o).unwrap_or(o.into()));
+        match state::last_run(&out_root, root) {
+            Some(time) => {
+                let time = chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M");
+                say!("🕒 Only files modified since the last run ({}).", time);
+            }
+            None => say!("🕒 No earlier run recorded; looking at every file."),
+        }
+    }
+    if opts.recursive {
+        match opts.max_depth {
+            Some(depth) => say!("🔁 Recursing into subfolders (max depth {}).", depth),
+            None => say!("🔁 Recursing into subfolders."),
+        }
+    }
+}
+
+/// Interactive `--interactive` state: once the user answers "a", stop asking.
+struct Prompter {
+    verb: &'static str,
+    yes_to_all: bool,
+    enabled: bool,
+}
+
+impl Prompter {
+    fn ask(&mut self, planned: &PlannedAction) -> Decision {
+        if !self.enabled || self.yes_to_all {
+            return Decision::Proceed;
+        }
+        let stdin = io::stdin();
+        loop {
+            let question = format!(
+                "❓ {} '{}' -> '{}'? [y]es/[n]o/[a]ll/[q]uit: ",
+                self.verb,
+                planned.source.display(),
+                planned.dest_dir.display()
+            );
+            eprint!("{}", output::style(&question, true));
+            let _ = io::stderr().flush();
+            let mut answer = String::new();
+            match stdin.lock().read_line(&mut answer) {
+                Ok(0) | Err(_) => return Decision::Stop,
+                Ok(_) => {}
+            }
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => return Decision::Proceed,
+                "n" | "no" => return Decision::Skip,
+                "a" | "all" => {
+                    self.yes_to_all = true;
+                    return Decision::Proceed;
+                }
+                "q" | "quit" => return Decision::Stop,
+                _ => eprintln!("Please answer y, n, a or q."),
+            }
+        }
+    }
+}
+
+/// `1234567` as `1,234,567`.
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// `--timing`: how long the run took and how fast files (and, when they were
+/// really copied or moved, bytes) went through.
+fn timing_line(summary: &Summary, dry_run: bool) -> String {
+    // A run too quick to measure would claim absurd speeds
+    let secs = summary.elapsed.as_secs_f64().max(0.001);
+    let files_per_sec = (summary.total_files() as f64 / secs).round() as usize;
+    let took = if secs < 1.0 { format!("{:.0} ms", secs * 1000.0) } else { format!("{:.1} s", secs) };
+    let mut line = format!("⏱️ Took {}: {} files/s", took, group_digits(files_per_sec));
+    if !dry_run {
+        let bytes_per_sec = (summary.total_bytes() as f64 / secs) as u64;
+        line.push_str(&format!(", {}/s", size::format_size(bytes_per_sec)));
+    }
+    line
+}
+
+/// What a run over `folders` (or the `listed` files) would do, from a dry
+/// run of it: its counts, and the space it needs on each drive. The actions
+/// themselves aren't kept.
+fn scan_run(
+    folders: &[(PathBuf, Vec<glob::Pattern>)],
+    listed: Option<&Vec<PathBuf>>,
+    opts: &Options,
+) -> (Summary, space::SpaceNeeds) {
+    let mut scan = opts.clone();
+    scan.dry_run = true;
+    scan.diff = false;
+    scan.keep_actions = false;
+    // Hashing everything twice isn't worth it; without it the estimate only errs on the big side
+    scan.dedup = false;
+    let mut planned = Summary::default();
+    let mut needed = space::SpaceNeeds::new(opts.move_files);
+    for (root, ignore) in folders {
+        scan.ignore = ignore.clone();
+        let on_action = |action: &FileAction| needed.add(action);
+        let folder = match listed {
+            Some(listed) => {
+                organize_files_with_callbacks(listed.clone(), &scan, |_| Decision::Proceed, |_, _| {}, on_action)
+            }
+            None => organize_with_callbacks(root, &scan, |_| Decision::Proceed, |_, _| {}, on_action),
+        };
+        // Problems reading a folder are reported by the run itself
+        let Ok(folder) = folder else { continue };
+        // --limit is for all folders together, as in the run itself
+        if let Some(left) = &mut scan.limit {
+            *left = left.saturating_sub(folder.limit_used);
+        }
+        let stopped = folder.stopped;
+        planned.merge(folder);
+        if stopped {
+            break;
+        }
+    }
+    (planned, needed)
+}
+
+/// Says what the `planned` run adds up to and asks for Enter before carrying
+/// it out. `false` if the user said no.
+fn confirm_run(planned: &Summary, opts: &Options) -> bool {
+    let files = planned.total_files();
+    if files == 0 {
+        return true;
+    }
+    let categories: Vec<&String> = planned.bytes.keys().filter(|category| planned.count(category) > 0).collect();
+    let question = format!(
+        "❓ Will {} {} {} ({}) into {} {}. Press Enter to go ahead, or n to stop: ",
+        opts.verb(),
+        group_digits(files),
+        if files == 1 { "file" } else { "files" },
+        size::format_size(planned.total_bytes()),
+        categories.len(),
+        if categories.len() == 1 { "category" } else { "categories" }
+    );
+    eprint!("{}", output::style(&question, true));
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes"),
+    }
+}
 
-    if args.len() < 2 {
-        print_usage_and_exit(program);
+/// Warns about every drive the `planned` files won't fit on; `true` if there was one.
+fn report_shortfalls(short: Vec<space::Shortfall>, opts: &Options) -> bool {
+    // Links take no room
+    if opts.link.is_some() {
+        return false;
     }
+    for shortfall in &short {
+        say_err!(
+            "⚠️ Not enough space for '{}': needs {}, but only {} is free",
+            shortfall.folder.display(),
+            size::format_size(shortfall.needed),
+            size::format_size(shortfall.available)
+        );
+    }
+    !short.is_empty()
+}
+
+/// Whether the summary of a run has to keep every action until it's over.
+/// Not for the reports written file by file as it goes (`live_reports`), but
+/// the tree, the script and the `--diff` counts look at them all at the end.
+fn keeps_actions(opts: &Options, live_reports: bool, tree: bool, emit_sh: bool) -> bool {
+    !live_reports || tree || emit_sh || opts.diff
+}
+
+/// The reports written file by file as the run goes: `--json` on stdout,
+/// the `--plan` file and the `--csv` file.
+struct Reports<'a> {
+    json: Option<report::JsonReport<BufWriter<io::Stdout>>>,
+    /// The plan and the path it's written to.
+    plan: Option<(PlanWriter, &'a str)>,
+    /// The CSV report and the path it's written to.
+    csv: Option<(report::CsvReport<BufWriter<File>>, &'a str)>,
+    /// Why writing stopped; nothing more is written after the first error.
+    error: Option<String>,
+}
+
+impl Reports<'_> {
+    fn add(&mut self, action: &FileAction) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Some(json) = &mut self.json
+            && let Err(e) = json.add(action)
+        {
+            self.error = Some(format!("cannot write the JSON report: {}", e));
+        }
+        if let Some((plan, path)) = &mut self.plan
+            && let Err(e) = plan.add(action)
+        {
+            self.error = Some(format!("cannot write plan '{}': {}", path, e));
+        }
+        if let Some((csv, path)) = &mut self.csv
+            && let Err(e) = csv.add(action)
+        {
+            self.error = Some(format!("cannot write the CSV report '{}': {}", path, e));
+        }
+    }
+}
+
+/// Single-line progress bar on stdout, redrawn in place as files are handled.
+struct ProgressBar {
+    total: usize,
+    last_draw: Option<Instant>,
+}
 
-    let folder_path = &args[1];
-    let dry_run = args.iter().any(|a| a == "--dry-run" || a == "-n");
+impl ProgressBar {
+    const WIDTH: usize = 30;
 
-    // Resolve canonical path (handles symlinks)
-    let path = Path::new(folder_path);
-    let canonical = match fs::canonicalize(path) {
-        Ok(p) => p,
-        Err(_) => {
-            eprintln!("❌ Error: '{}' is not a valid directory!", folder_path);
+    fn update(&mut self, done: usize, current: &Path) {
+        // Redrawing for every file would make the terminal the bottleneck
+        let finished = done >= self.total;
+        if !finished && self.last_draw.is_some_and(|t| t.elapsed() < Duration::from_millis(50)) {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+
+        let fraction = if self.total == 0 { 1.0 } else { (done as f64 / self.total as f64).min(1.0) };
+        let filled = (fraction * Self::WIDTH as f64) as usize;
+        let name = current.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        print!(
+            "\r\x1b[2K[{}{}] {:>3}% ({}/{}) {}",
+            "#".repeat(filled),
+            "-".repeat(Self::WIDTH - filled),
+            (fraction * 100.0) as usize,
+            done,
+            self.total,
+            name
+        );
+        let _ = io::stdout().flush();
+    }
+
+    /// Removes the bar so normal output can continue on a clean line.
+    fn clear(&self) {
+        if self.last_draw.is_some() {
+            print!("\r\x1b[2K");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+/// Number of largest files `--stats-only` lists unless `--top` says otherwise.
+const DEFAULT_TOP: usize = 10;
+
+/// Runs `--stats-only` and prints the folder's make-up per category.
+fn run_stats(root: &Path, opts: &Options, top: usize, json: bool) {
+    let stats = match folder_stats(root, opts, top) {
+        Ok(stats) => stats,
+        Err(e) => {
+            say_err!("❌ Failed to read directory: {}", e);
             std::process::exit(1);
         }
     };
+    if json {
+        println!("{}", report::stats_json(root, &stats));
+    } else {
+        say!("📁 Scanning folder: {}", root.display());
+        print_problems(&stats.failures, opts.verb());
 
-    if !canonical.is_dir() {
-        eprintln!("❌ Error: '{}' is not a directory!", canonical.display());
+        let rows = stats.rows();
+        let total_bytes = stats.total_bytes();
+        let width = rows.iter().map(|(key, _, _)| opts.folder_name(key).chars().count()).max().unwrap_or(0);
+        say!("\n📊 Folder composition:");
+        for (key, count, bytes) in rows {
+            let files = if count == 1 { "file" } else { "files" };
+            let share = if total_bytes == 0 { 0.0 } else { bytes as f64 * 100.0 / total_bytes as f64 };
+            let name = opts.folder_name(key);
+            println!("  - {:<width$} : {} {} ({}, {:.0}%)", name, count, files, size::format_size(bytes), share);
+        }
+        println!("  Total: {} files ({})", stats.total_files(), size::format_size(total_bytes));
+        if stats.skipped > 0 {
+            println!("  Skipped: {}", stats.skipped);
+        }
+        if !stats.failures.is_empty() {
+            println!("  Errors: {}", stats.failures.len());
+        }
+
+        if !stats.largest.is_empty() {
+            say!("\n🏋️ Largest files:");
+            for (i, file) in stats.largest.iter().enumerate() {
+                let size = size::format_size(file.size);
+                let name = opts.folder_name(&file.category);
+                println!("  {:>2}. {:>10}  {} ({})", i + 1, size, file.path.display(), name);
+            }
+        }
+    }
+    if !stats.failures.is_empty() {
+        std::process::exit(EXIT_FILE_ERRORS);
+    }
+}
+
+/// Runs `--extension-report`: every extension in the folder with its file
+/// count, size and the category it goes to, most common first.
+fn run_extension_report(root: &Path, opts: &Options, json: bool) {
+    let stats = match folder_stats(root, opts, 0) {
+        Ok(stats) => stats,
+        Err(e) => {
+            say_err!("❌ Failed to read directory: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if json {
+        println!("{}", report::extensions_json(root, &stats));
+    } else {
+        say!("📁 Scanning folder: {}", root.display());
+        print_problems(&stats.failures, opts.verb());
+
+        let rows = stats.extension_rows();
+        let label = |ext: &str| if ext.is_empty() { "(none)".to_string() } else { format!(".{}", ext) };
+        let width = rows.iter().map(|(ext, _)| label(ext).chars().count()).max().unwrap_or(0).max(9);
+        say!("\n📋 Extensions ({}):", rows.len());
+        println!("  {:<width$}  {:>6}  {:>10}  Category", "Extension", "Files", "Size");
+        for (ext, count) in rows {
+            let size = size::format_size(count.bytes);
+            let category = opts.folder_name(&count.category);
+            println!("  {:<width$}  {:>6}  {:>10}  {}", label(ext), count.files, size, category);
+        }
+        if stats.skipped > 0 {
+            println!("  Skipped: {}", stats.skipped);
+        }
+        if !stats.failures.is_empty() {
+            println!("  Errors: {}", stats.failures.len());
+        }
+    }
+    if !stats.failures.is_empty() {
+        std::process::exit(EXIT_FILE_ERRORS);
+    }
+}
+
+/// Runs `--report-only-others`: every file that no category matches, with
+/// its extension, sorted by extension and then by name.
+fn run_others_report(root: &Path, opts: &Options, json: bool) {
+    let stats = match folder_stats(root, opts, 0) {
+        Ok(stats) => stats,
+        Err(e) => {
+            say_err!("❌ Failed to read directory: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if json {
+        println!("{}", report::others_json(root, &stats));
+    } else {
+        say!("📁 Scanning folder: {}", root.display());
+        print_problems(&stats.failures, opts.verb());
+
+        let rows = stats.others_rows();
+        let label = |ext: &str| if ext.is_empty() { "(none)".to_string() } else { format!(".{}", ext) };
+        let width = rows.iter().map(|file| label(&file.ext).chars().count()).max().unwrap_or(0).max(9);
+        say!("\n📋 Files that would go to {} ({}):", opts.folder_name("Others"), rows.len());
+        if !rows.is_empty() {
+            println!("  {:<width$}  {:>10}  File", "Extension", "Size");
+        }
+        for file in rows {
+            println!("  {:<width$}  {:>10}  {}", label(&file.ext), size::format_size(file.size), file.path.display());
+        }
+        if stats.skipped > 0 {
+            println!("  Skipped: {}", stats.skipped);
+        }
+        if !stats.failures.is_empty() {
+            println!("  Errors: {}", stats.failures.len());
+        }
+    }
+    if !stats.failures.is_empty() {
+        std::process::exit(EXIT_FILE_ERRORS);
+    }
+}
+
+/// Reads the paths given on stdin for `--stdin` / `--null`.
+fn read_stdin_paths(separator: u8) -> Vec<PathBuf> {
+    let mut data = Vec::new();
+    if let Err(e) = io::stdin().lock().read_to_end(&mut data) {
+        say_err!("❌ Error: cannot read file list from stdin: {}", e);
         std::process::exit(1);
     }
+    parse_path_list(&data, separator)
+}
 
-    println!("📁 Organizing folder: {}", canonical.display());
+/// Runs `--undo LOGFILE` and prints what was reverted.
+fn run_undo(log: &Path, dry_run: bool, verbosity: Verbosity) {
+    say!("⏪ Undoing operations from log: {}", log.display());
     if dry_run {
-        println!("🔎 Running in DRY-RUN mode (no files will be copied).");
-    } else {
-        println!("⚠️ Safe Mode: files will be COPIED (originals left intact).");
+        say!("🔎 Running in DRY-RUN mode (nothing will be changed).");
     }
+    let summary = match undo::undo(log, dry_run) {
+        Ok(summary) => summary,
+        Err(e) => {
+            say_err!("❌ Error: cannot read log '{}': {}", log.display(), e);
+            std::process::exit(1);
+        }
+    };
 
-    let categories = build_category_map();
+    for action in &summary.actions {
+        let entry = &action.entry;
+        match &action.status {
+            UndoStatus::Skipped(reason) => {
+                say_err!("⚠️ Skipped '{}': {}", entry.destination.display(), reason)
+            }
+            _ if verbosity == Verbosity::Quiet => {}
+            UndoStatus::Deleted => say!("🗑️ Removed copy: '{}'", entry.destination.display()),
+            UndoStatus::Restored => say!(
+                "↩️ Restored: '{}' -> '{}'",
+                entry.destination.display(),
+                entry.source.display()
+            ),
+            UndoStatus::WouldUndo => say!("➡️ Would undo {}: '{}'", entry.action, entry.destination.display()),
+        }
+    }
 
-    // Counters for summary
-    let mut counters: HashMap<String, usize> = HashMap::new();
-    counters.insert("Images".to_string(), 0);
-    counters.insert("Documents".to_string(), 0);
-    counters.insert("Videos".to_string(), 0);
-    counters.insert("Audio".to_string(), 0);
-    counters.insert("Archives".to_string(), 0);
-    counters.insert("Code".to_string(), 0);
-    counters.insert("Others".to_string(), 0);
-    counters.insert("Errors".to_string(), 0);
+    if verbosity != Verbosity::Quiet {
+        say!("\n📊 Undo summary:");
+    }
+    println!("  - Undone  : {}", summary.undone());
+    println!("  - Skipped : {}", summary.skipped());
+}
 
-    // Iterate entries in the directory (non-recursive)
-    let read_dir = match fs::read_dir(&canonical) {
-        Ok(rd) => rd,
+/// Runs `--apply PLAN` and prints what was done.
+fn run_apply(plan_path: &Path, dry_run: bool, log: Option<&Path>, verbosity: Verbosity) {
+    if verbosity != Verbosity::Quiet {
+        say!("📋 Applying plan: {}", plan_path.display());
+        if dry_run {
+            say!("🔎 Running in DRY-RUN mode (nothing will be changed).");
+        }
+    }
+    let plan = match Plan::read(plan_path) {
+        Ok(plan) => plan,
         Err(e) => {
-            eprintln!("❌ Failed to read directory: {}", e);
+            say_err!("❌ Error: cannot read plan '{}': {}", plan_path.display(), e);
             std::process::exit(1);
         }
     };
+    let mut log = match log {
+        Some(log) if !dry_run => match OperationLog::open(log) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                say_err!("❌ Error: cannot open log file '{}': {}", log.display(), e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+    let summary = plan::apply_plan(&plan, dry_run, log.as_mut());
 
-    for entry in read_dir {
-        match entry {
-            Ok(dir_entry) => {
-                let file_type = match dir_entry.file_type() {
-                    Ok(ft) => ft,
+    for action in &summary.actions {
+        let entry = &action.entry;
+        let (src, dest) = (entry.source.display(), entry.destination.display());
+        match &action.status {
+            ApplyStatus::Skipped(reason) => say_err!("⚠️ Skipped '{}': {}", src, reason),
+            ApplyStatus::Failed(e) => say_err!("❌ Failed to {} '{}': {}", plan.action, src, e),
+            _ if verbosity == Verbosity::Quiet => {}
+            ApplyStatus::Copied => say!("✅ Copied: '{}' -> '{}'", src, dest),
+            ApplyStatus::Moved => say!("✅ Moved: '{}' -> '{}'", src, dest),
+            ApplyStatus::WouldApply => say!("➡️ Would {}: '{}' -> '{}'", plan.action, src, dest),
+        }
+        if let Some(warning) = &action.warning {
+            say_err!("⚠️ '{}': {}", src, warning);
+        }
+    }
+
+    if verbosity != Verbosity::Quiet {
+        say!("\n📊 Apply summary:");
+    }
+    println!("  - Applied : {}", summary.applied());
+    println!("  - Skipped : {}", summary.skipped());
+    println!("  - Errors  : {}", summary.failed());
+    if summary.failed() > 0 {
+        std::process::exit(EXIT_FILE_ERRORS);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args.first().map(|s| s.as_str()).unwrap_or("file_organizer");
+    // Looked up first so that errors about the other arguments are styled too
+    let color = match args.iter().position(|arg| arg == "--color").map(|i| args.get(i + 1)) {
+        None => ColorChoice::Auto,
+        Some(value) => match value.map(|v| v.parse::<ColorChoice>()) {
+            Some(Ok(choice)) => choice,
+            Some(Err(e)) => {
+                eprintln!("❌ Error: {}", e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("❌ Error: --color expects a value");
+                std::process::exit(1);
+            }
+        },
+    };
+    output::init(color);
+
+    let mut folder_paths: Vec<String> = Vec::new();
+    let mut config_path: Option<PathBuf> = None;
+    let mut no_config = false;
+    let mut profile: Option<&String> = None;
+    let mut list_profiles = false;
+    let mut merge_config = false;
+    let mut mappings: Vec<&String> = Vec::new();
+    let mut priority: Option<Vec<String>> = None;
+    let mut rules_path: Option<&String> = None;
+    let mut force = false;
+    let mut yes = false;
+    let mut json = false;
+    let mut summary_json = false;
+    let mut interactive = false;
+    let mut verbosity = Verbosity::Normal;
+    let mut timing = false;
+    let mut rename_blocked = false;
+    let mut progress = true;
+    let mut undo_log: Option<&String> = None;
+    let mut plan_out: Option<&String> = None;
+    let mut flatten = false;
+    let mut summary_out: Option<PathBuf> = None;
+    let mut csv_out: Option<&String> = None;
+    let mut apply_plan: Option<&String> = None;
+    let mut stats_only = false;
+    let mut extension_report = false;
+    let mut others_only = false;
+    let mut tree = false;
+    let mut emit_sh = false;
+    let mut use_ignore_file = true;
+    let mut list_separator: Option<u8> = None;
+    let mut top = DEFAULT_TOP;
+    let mut opts = Options::default();
+
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--dry-run" | "-n" => opts.dry_run = true,
+            "--move" | "-m" => opts.move_files = true,
+            "--recursive" | "-r" => opts.recursive = true,
+            "--max-depth" => match flag_value(arg, &mut rest).parse::<usize>() {
+                Ok(depth) => {
+                    opts.max_depth = Some(depth);
+                    opts.recursive = true;
+                }
+                Err(_) => {
+                    say_err!("❌ Error: --max-depth expects a non-negative number");
+                    std::process::exit(1);
+                }
+            },
+            "--config" => config_path = Some(PathBuf::from(flag_value(arg, &mut rest))),
+            "--no-config" => no_config = true,
+            "--profile" => profile = Some(flag_value(arg, &mut rest)),
+            "--list-profiles" => list_profiles = true,
+            "--merge-config" => merge_config = true,
+            "--map" => mappings.push(flag_value(arg, &mut rest)),
+            "--rules" => rules_path = Some(flag_value(arg, &mut rest)),
+            "--priority" => {
+                let list = flag_value(arg, &mut rest).split(',').map(|c| c.trim().to_string());
+                priority = Some(list.filter(|c| !c.is_empty()).collect());
+            }
+            "--json" => json = true,
+            "--summary-json" => summary_json = true,
+            "--tree" => tree = true,
+            "--emit-sh" => {
+                emit_sh = true;
+                opts.dry_run = true;
+            }
+            "--color" => {
+                flag_value(arg, &mut rest);
+            }
+            "--interactive" | "-i" => interactive = true,
+            "--dedup" => opts.dedup = true,
+            "--undo" => undo_log = Some(flag_value(arg, &mut rest)),
+            "--diff" => {
+                opts.diff = true;
+                opts.dry_run = true;
+            }
+            "--plan" => {
+                plan_out = Some(flag_value(arg, &mut rest));
+                opts.dry_run = true;
+            }
+            "--apply" => apply_plan = Some(flag_value(arg, &mut rest)),
+            "--csv" => csv_out = Some(flag_value(arg, &mut rest)),
+            "--include" => opts.include.push(glob_pattern(arg, flag_value(arg, &mut rest))),
+            "--exclude" => opts.exclude.push(glob_pattern(arg, flag_value(arg, &mut rest))),
+            "--group-small-files" => {
+                opts.small_files = opts.small_files.or(Some(DEFAULT_SMALL_THRESHOLD));
+            }
+            "--small-threshold" => opts.small_files = Some(size_value(arg, flag_value(arg, &mut rest))),
+            "--skip-empty" => opts.skip_empty = true,
+            "--min-size" => opts.min_size = Some(size_value(arg, flag_value(arg, &mut rest))),
+            "--max-size" => opts.max_size = Some(size_value(arg, flag_value(arg, &mut rest))),
+            "--since" => opts.modified_since = Some(date_value(arg, flag_value(arg, &mut rest), Bound::Start)),
+            "--since-last-run" => opts.since_last_run = true,
+            "--until" => opts.modified_until = Some(date_value(arg, flag_value(arg, &mut rest), Bound::End)),
+            "--jobs" | "-j" => match flag_value(arg, &mut rest).parse::<usize>() {
+                Ok(jobs) if jobs > 0 => opts.jobs = jobs,
+                _ => {
+                    say_err!("❌ Error: --jobs expects a number greater than 0");
+                    std::process::exit(1);
+                }
+            },
+            "--log" => opts.log = Some(PathBuf::from(flag_value(arg, &mut rest))),
+            "--exec" | "--exec-shell" => {
+                if arg == "--exec-shell" && cfg!(not(unix)) {
+                    say_err!("❌ Error: --exec-shell needs a Unix shell; use --exec on this platform");
+                    std::process::exit(1);
+                }
+                match exec::Hook::parse(flag_value(arg, &mut rest), arg == "--exec-shell") {
+                    Ok(hook) => opts.exec = Some(hook),
                     Err(e) => {
-                        eprintln!("⚠️ Could not read file type: {}", e);
-                        *counters.get_mut("Errors").unwrap() += 1;
-                        continue;
+                        say_err!("❌ Error: {}", e);
+                        std::process::exit(1);
                     }
-                };
-
-                // Skip directories; we only process regular files
-                if file_type.is_dir() {
-                    continue;
                 }
-                if file_type.is_symlink() {
-                    // skip symlinks for safety
-                    continue;
+            }
+            "--quiet" | "-q" => verbosity = Verbosity::Quiet,
+            "--verbose" | "-v" => verbosity = Verbosity::Verbose,
+            "--timing" => timing = true,
+            "--rename-blocked-folders" => rename_blocked = true,
+            "--no-progress" => progress = false,
+            "--preserve-times" => opts.preserve_times = true,
+            "--preserve" => match flag_value(arg, &mut rest).as_str() {
+                "times" => opts.preserve_times = true,
+                "all" => {
+                    opts.preserve_times = true;
+                    opts.preserve_metadata = true;
+                }
+                other => {
+                    say_err!("❌ Error: unknown --preserve value '{}' (expected times or all)", other);
+                    std::process::exit(1);
+                }
+            },
+            "--verify" => opts.verify = true,
+            "--link" => match flag_value(arg, &mut rest).parse::<LinkKind>() {
+                Ok(kind) => opts.link = Some(kind),
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            "--case-sensitive" => opts.case_sensitive = true,
+            "--sniff-content" => opts.sniff_content = true,
+            "--by-mime" => opts.by_mime = true,
+            "--flatten" => {
+                opts.recursive = true;
+                flatten = true;
+            }
+            "--keep-structure" => {
+                opts.recursive = true;
+                opts.keep_structure = true;
+            }
+            "--flatten-remove-empty" => {
+                opts.recursive = true;
+                opts.remove_empty_dirs = EmptyDirs::All;
+                flatten = true;
+            }
+            "--prune-empty-dirs" => opts.remove_empty_dirs = EmptyDirs::Emptied,
+            "--prune-all-empty" => opts.remove_empty_dirs = EmptyDirs::All,
+            "--conflict-format" => match ConflictFormat::parse(flag_value(arg, &mut rest)) {
+                Ok(format) => opts.conflict_format = format,
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            "--rename" => match rename::parse_rules(flag_value(arg, &mut rest)) {
+                Ok(rules) => opts.rename = rules,
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            "--normalize-unicode" => opts.normalize_unicode = true,
+            "--extract-archives" => opts.extract_archives = true,
+            "--extract-nested" => opts.extract_nested = true,
+            "--summary-out" => summary_out = Some(PathBuf::from(flag_value(arg, &mut rest))),
+            "--fail-fast" => opts.fail_fast = true,
+            "--force" => force = true,
+            "--yes" | "-y" => yes = true,
+            "--transactional" => opts.transactional = true,
+            "--limit" => match flag_value(arg, &mut rest).parse::<usize>() {
+                Ok(limit) if limit > 0 => opts.limit = Some(limit),
+                _ => {
+                    say_err!("❌ Error: --limit expects a number greater than 0");
+                    std::process::exit(1);
                 }
+            },
+            "--retries" => match flag_value(arg, &mut rest).parse::<u32>() {
+                Ok(retries) => opts.retries = retries,
+                Err(_) => {
+                    say_err!("❌ Error: --retries expects a number of times to try again");
+                    std::process::exit(1);
+                }
+            },
+            "--stats-only" => stats_only = true,
+            "--extension-report" => extension_report = true,
+            "--report-only-others" => {
+                extension_report = true;
+                others_only = true;
+            }
+            "--no-ignore" => use_ignore_file = false,
+            "--stdin" => list_separator = list_separator.or(Some(b'\n')),
+            "--null" | "-0" => list_separator = Some(0),
+            "--top" => match flag_value(arg, &mut rest).parse::<usize>() {
+                Ok(n) => top = n,
+                Err(_) => {
+                    say_err!("❌ Error: --top expects a non-negative number");
+                    std::process::exit(1);
+                }
+            },
+            "--follow-symlinks" => opts.follow_symlinks = true,
+            "--subcategorize" => opts.subcategorize = true,
+            "--skip-hidden" => opts.hidden = HiddenFiles::Skip,
+            "--hidden-category" => opts.hidden = HiddenFiles::Separate,
+            "--others" => match flag_value(arg, &mut rest).parse::<OthersPolicy>() {
+                Ok(policy) => opts.others = policy,
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            "--trash-on-overwrite" => opts.trash_on_overwrite = true,
+            "--on-conflict" => match flag_value(arg, &mut rest).parse::<ConflictPolicy>() {
+                Ok(policy) => opts.on_conflict = policy,
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            "--by-date" => opts.by_date = true,
+            "--by-owner" => {
+                if cfg!(not(unix)) {
+                    say_err!("❌ Error: --by-owner needs Unix file owners and isn't supported on this platform");
+                    std::process::exit(1);
+                }
+                opts.by_owner = true;
+            }
+            "--by-exif-date" => {
+                opts.by_exif_date = true;
+                opts.by_date = true;
+            }
+            "--date-field" => match flag_value(arg, &mut rest).parse::<DateField>() {
+                Ok(field) => {
+                    opts.date_field = field;
+                    opts.by_date = true;
+                }
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            "--by-prefix" => opts.by_prefix = true,
+            "--prefix-min" => match flag_value(arg, &mut rest).parse::<usize>() {
+                Ok(min) if min >= 2 => {
+                    opts.prefix_min = min;
+                    opts.by_prefix = true;
+                }
+                _ => {
+                    say_err!("❌ Error: --prefix-min expects a number of at least 2");
+                    std::process::exit(1);
+                }
+            },
+            "--tag-pattern" => match TagPattern::parse(flag_value(arg, &mut rest)) {
+                Ok(pattern) => opts.tag_pattern = Some(pattern),
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            "--date-only" => {
+                opts.by_date = true;
+                opts.date_only = true;
+            }
+            "--date-format" => {
+                let format = flag_value(arg, &mut rest);
+                if let Err(e) = dates::validate_date_format(format) {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+                opts.date_format = format.clone();
+                opts.by_date = true;
+            }
+            "--output" | "-o" => opts.output = Some(PathBuf::from(flag_value(arg, &mut rest))),
+            "--help" | "-h" => print_help_and_exit(program),
+            "--version" | "-V" => {
+                println!("{} {}", BIN_NAME, env!("CARGO_PKG_VERSION"));
+                return;
+            }
+            "--generate-completions" => match completions::generate(flag_value(arg, &mut rest), BIN_NAME) {
+                Ok(script) => {
+                    print!("{}", script);
+                    return;
+                }
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            _ if arg.len() > 1 && arg.starts_with('-') && !completions::is_known(arg) => {
+                say_err!("❌ Error: unknown option '{}'", arg);
+                print_usage_and_exit(program);
+            }
+            _ => folder_paths.push(arg.clone()),
+        }
+    }
 
-                let path = dir_entry.path();
-                let ext = file_extension_lowercase(&path);
-                let category = category_for_extension(&ext, &categories);
-                let dest_dir = canonical.join(category);
+    if let Some(log) = undo_log {
+        run_undo(Path::new(log), opts.dry_run, verbosity);
+        return;
+    }
+    if let Some(plan) = apply_plan {
+        run_apply(Path::new(plan), opts.dry_run, opts.log.as_deref(), verbosity);
+        return;
+    }
 
-                if dry_run {
-                    println!("➡️ Would copy: '{}' -> '{}'", path.display(), dest_dir.display());
-                    *counters.get_mut(category).unwrap() += 1;
-                    continue;
+    // Without --config, a personal config is used if there is one
+    if config_path.is_none() && !no_config {
+        config_path = config::default_config_path().filter(|path| path.is_file());
+        if let (Some(path), Verbosity::Verbose) = (&config_path, verbosity) {
+            say!("⚙️ Using config '{}' (--no-config to ignore it)", path.display());
+        }
+    }
+    // Loaded before the checks below, since a profile can turn on options they look at
+    let mut cfg = config_path.map(|path| match config::load_config_with_case(&path, opts.case_sensitive) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            say_err!("❌ Error: {}", e);
+            std::process::exit(1);
+        }
+    });
+    if list_profiles {
+        let names = cfg.as_ref().map(|cfg| cfg.profile_names()).unwrap_or_default();
+        if names.is_empty() {
+            say_err!("No profiles; add [profiles.NAME] sections to the config.");
+        }
+        for name in names {
+            println!("{}", name);
+        }
+        return;
+    }
+    if let Some(name) = profile {
+        let Some(cfg) = cfg.as_mut() else {
+            say_err!("❌ Error: --profile needs a config with [profiles.{}], and none was found", name);
+            std::process::exit(1);
+        };
+        match cfg.apply_profile(name) {
+            Ok(profile) => {
+                if opts.output.is_none() {
+                    opts.output = profile.output;
                 }
+                opts.recursive |= profile.recursive;
+                opts.move_files |= profile.move_files;
+                opts.by_date |= profile.by_date;
+                opts.dedup |= profile.dedup;
+            }
+            Err(e) => {
+                say_err!("❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
-                match copy_file_to_category(&path, &dest_dir) {
-                    Ok(dest_path) => {
-                        println!("✅ Copied: '{}' -> '{}'", path.display(), dest_path.display());
-                        *counters.get_mut(category).unwrap() += 1;
+    if opts.trash_on_overwrite && !opts.on_conflict.replaces() {
+        say_err!("⚠️ --trash-on-overwrite has no effect without --on-conflict overwrite or newest");
+    }
+    if opts.extract_nested && !opts.extract_archives {
+        say_err!("⚠️ --extract-nested has no effect without --extract-archives");
+    }
+    if opts.link.is_some() && opts.move_files {
+        say_err!("❌ Error: --link leaves the files where they are, so it can't go with --move; use one of them");
+        std::process::exit(1);
+    }
+    if opts.link.is_some() && plan_out.is_some() {
+        say_err!("❌ Error: plans only record copies and moves; --link can't be used with --plan");
+        std::process::exit(1);
+    }
+    if opts.link.is_some() && (opts.verify || opts.preserve_times) {
+        say_err!("⚠️ A link has the contents and timestamps of its file; ignoring --verify and --preserve-times");
+        (opts.verify, opts.preserve_times, opts.preserve_metadata) = (false, false, false);
+    }
+    if opts.verify && opts.move_files {
+        say_err!("⚠️ --verify only checks copies, and --move leaves no original to compare with; ignoring it");
+        opts.verify = false;
+    }
+    if opts.transactional && !opts.move_files {
+        say_err!("⚠️ --transactional only rolls back --move; ignoring it");
+        opts.transactional = false;
+    }
+    if opts.transactional && opts.on_conflict.replaces() {
+        say_err!("❌ Error: --transactional can't restore files replaced by --on-conflict {}", opts.on_conflict);
+        std::process::exit(1);
+    }
+    if opts.by_prefix && opts.date_only {
+        say_err!("❌ Error: --by-prefix groups files inside their category, so it can't be used with --date-only");
+        std::process::exit(1);
+    }
+    if let (Some(since), Some(until)) = (opts.modified_since, opts.modified_until)
+        && since > until
+    {
+        say_err!("❌ Error: --since is after --until, so no file could match");
+        std::process::exit(1);
+    }
+
+    let verb = opts.verb();
+    // With --stdin there's no folder to walk; everything goes into --output,
+    // which stands in for the folder in the header and reports
+    let mut stdin_files = list_separator.map(read_stdin_paths);
+    let mut bad_folders = 0;
+    // Each folder with the patterns of its own .organizerignore
+    let folders: Vec<(PathBuf, Vec<glob::Pattern>)> = if stdin_files.is_some() {
+        if !folder_paths.is_empty() {
+            say_err!("❌ Error: with --stdin the files come from stdin; give the destination with --output");
+            std::process::exit(1);
+        }
+        if stats_only || extension_report || interactive {
+            say_err!("❌ Error: --stdin can't be combined with --stats-only, --extension-report or --interactive");
+            std::process::exit(1);
+        }
+        if opts.keep_structure {
+            say_err!("⚠️ --keep-structure needs a folder to keep the structure of; ignoring it with --stdin");
+            opts.keep_structure = false;
+        }
+        if opts.since_last_run {
+            say_err!("⚠️ --since-last-run remembers runs over a folder; ignoring it with --stdin");
+            opts.since_last_run = false;
+        }
+        let Some(output) = &opts.output else {
+            say_err!("❌ Error: --stdin needs --output to know where to put the files");
+            std::process::exit(1);
+        };
+        vec![(std::path::absolute(output).unwrap_or_else(|_| output.clone()), Vec::new())]
+    } else {
+        // The command line wins over the environment
+        if folder_paths.is_empty() {
+            match env::var(DEFAULT_DIR_VAR) {
+                Ok(dir) if !dir.is_empty() => folder_paths.push(dir),
+                _ => print_usage_and_exit(program),
+            }
+        }
+        // All folders are checked before any is organized; bad ones are left out
+        let read_only = stats_only || extension_report || (opts.dry_run && plan_out.is_none());
+        let mut folders: Vec<(PathBuf, Vec<glob::Pattern>)> = Vec::new();
+        for folder_path in &folder_paths {
+            let checked = resolve_folder(folder_path).and_then(|root| {
+                if let (false, false, Some(what)) = (force, read_only, dangerous_folder(&root)) {
+                    return Err(format!(
+                        "'{}' is {}; organizing it would scatter dotfiles and system files into category folders. \
+                         Add --force if you really mean to",
+                        root.display(),
+                        what
+                    ));
+                }
+                let ignore = if use_ignore_file { ignore::load_ignore_file(&root)? } else { Vec::new() };
+                Ok((root, ignore))
+            });
+            match checked {
+                Ok(folder) if folders.iter().any(|(root, _)| *root == folder.0) => {}
+                Ok(folder) => folders.push(folder),
+                Err(e) => {
+                    say_err!("❌ Error: {}", e);
+                    if opts.fail_fast || folder_paths.len() == 1 {
+                        std::process::exit(1);
                     }
-                    Err(e) => {
-                        eprintln!("❌ Failed to copy '{}': {}", path.display(), e);
-                        *counters.get_mut("Errors").unwrap() += 1;
+                    bad_folders += 1;
+                }
+            }
+        }
+        if folders.is_empty() {
+            say_err!("❌ Error: none of the folders can be organized");
+            std::process::exit(1);
+        }
+        folders
+    };
+    if folders.len() > 1 && (stats_only || extension_report || plan_out.is_some() || json) {
+        say_err!("❌ Error: --stats-only, --extension-report, --plan and --json work on a single folder");
+        std::process::exit(1);
+    }
+    let canonical = &folders[0].0;
+
+    // Categories a config may name besides its own and "Others"
+    let extra_categories = [HIDDEN_CATEGORY, SMALL_FILES_CATEGORY];
+    if let Some(cfg) = cfg {
+        if merge_config {
+            opts.categories = config::merge_categories(build_category_map(), cfg.categories);
+            if let Some(subs) = cfg.subcategories {
+                opts.subcategories = config::merge_subcategories(build_subcategory_map(), subs);
+            }
+        } else {
+            // A config with only profiles or folder names keeps the built-in categories
+            if !cfg.categories.is_empty() {
+                opts.categories = cfg.categories;
+            }
+            if let Some(subs) = cfg.subcategories {
+                opts.subcategories = subs;
+            }
+        }
+        if let Err(e) = config::validate_folder_names(&opts.categories, &cfg.folder_names, &extra_categories) {
+            say_err!("❌ Error: {}", e);
+            std::process::exit(1);
+        }
+        opts.folder_names = cfg.folder_names;
+        if let Err(e) = config::validate_destinations(&opts.categories, &cfg.destinations, &extra_categories) {
+            say_err!("❌ Error: {}", e);
+            std::process::exit(1);
+        }
+        opts.destinations = cfg.destinations;
+        opts.priority = cfg.priority;
+    } else if merge_config {
+        say_err!("⚠️ --merge-config has no effect without a config");
+    }
+    if !mappings.is_empty() {
+        let mut mapped = HashMap::new();
+        for spec in mappings {
+            if let Err(e) = config::add_mapping(&mut mapped, spec, opts.case_sensitive) {
+                say_err!("❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        opts.categories = config::merge_categories(std::mem::take(&mut opts.categories), mapped);
+    }
+    if let Some(path) = rules_path {
+        opts.rules = match rules::load_rules(Path::new(path)) {
+            Ok(rules) => rules,
+            Err(e) => {
+                say_err!("❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        // New categories get a folder and a summary row like any other
+        for rule in &opts.rules {
+            opts.categories.entry(rule.category.clone()).or_default();
+        }
+    }
+    if let Some(priority) = priority {
+        opts.priority = priority;
+    }
+    if let Err(e) = config::validate_priority(&opts.categories, &opts.priority) {
+        say_err!("❌ Error: {}", e);
+        std::process::exit(1);
+    }
+    if opts.remove_empty_dirs != EmptyDirs::Keep && !opts.move_files {
+        say_err!("⚠️ Empty folders are only removed after --move; copying leaves every folder in place");
+    }
+    // Checked here so the order of the flags doesn't matter
+    if flatten && opts.max_depth.is_some() {
+        say_err!("⚠️ --max-depth has no effect with --flatten");
+        opts.max_depth = None;
+    }
+    if flatten && opts.keep_structure {
+        say_err!("❌ Error: --flatten and --keep-structure contradict each other; use one of them");
+        std::process::exit(1);
+    }
+    if json && summary_json {
+        say_err!("❌ Error: --json already includes the counts of --summary-json; use one of them");
+        std::process::exit(1);
+    }
+    if tree && (!opts.dry_run || json || summary_json) {
+        say_err!("⚠️ --tree only applies to dry runs without --json or --summary-json; ignoring it");
+        tree = false;
+    }
+    if emit_sh && (json || summary_json || tree || plan_out.is_some()) {
+        say_err!("❌ Error: --emit-sh prints a script, not a report; drop --json, --summary-json, --tree and --plan");
+        std::process::exit(1);
+    }
+    if emit_sh && opts.extract_archives {
+        say_err!("❌ Error: files inside archives can't be copied by a script; use --emit-sh or --extract-archives");
+        std::process::exit(1);
+    }
+
+    if stats_only || extension_report {
+        let flag = if stats_only {
+            "--stats-only"
+        } else if others_only {
+            "--report-only-others"
+        } else {
+            "--extension-report"
+        };
+        if opts.move_files || interactive || plan_out.is_some() {
+            say_err!("⚠️ {} doesn't touch any files; ignoring --move, --interactive and --plan", flag);
+        }
+        if summary_json {
+            say_err!("⚠️ {} has its own report; ignoring --summary-json (use --json)", flag);
+        }
+        opts.ignore = folders[0].1.clone();
+        if others_only {
+            run_others_report(canonical, &opts, json);
+        } else if extension_report {
+            run_extension_report(canonical, &opts, json);
+        } else {
+            run_stats(canonical, &opts, top, json);
+        }
+        return;
+    }
+
+    // Only the JSON or the script goes to stdout
+    if summary_json || emit_sh {
+        output::say_to_stderr();
+    }
+
+    // Fail now rather than mid-run if the log can't be written
+    if let (Some(log), false) = (&opts.log, opts.dry_run)
+        && let Err(e) = OperationLog::open(log)
+    {
+        say_err!("❌ Error: cannot open log file '{}': {}", log.display(), e);
+        std::process::exit(1);
+    }
+
+    let mut prompter = Prompter {
+        verb: if opts.move_files {
+            "Move"
+        } else if opts.link.is_some() {
+            "Link"
+        } else {
+            "Copy"
+        },
+        yes_to_all: false,
+        enabled: interactive,
+    };
+    if interactive && opts.jobs > 1 {
+        say_err!("⚠️ --interactive prompts one file at a time; ignoring --jobs.");
+        opts.jobs = 1;
+    }
+    let non_tty = interactive && !opts.dry_run && !io::stdin().is_terminal();
+    if non_tty {
+        say_err!("⚠️ --interactive needs a terminal on stdin; answering \"no\" for every file.");
+    }
+    // A file in the way of a category folder would fail every file of that category
+    for (root, _) in &folders {
+        let blocked = match blocked_folders(root, &opts) {
+            Ok(blocked) => blocked,
+            // Reported when the folder is organized
+            Err(_) => continue,
+        };
+        for (category, file) in blocked {
+            if !rename_blocked || opts.destinations.contains_key(&category) {
+                say_err!(
+                    "❌ Error: '{}' is a file, so the {} folder can't be created there; move or rename it{}",
+                    file.display(),
+                    category,
+                    if opts.destinations.contains_key(&category) { "" } else { ", or use --rename-blocked-folders" }
+                );
+                std::process::exit(1);
+            }
+            let name = free_folder_name(&file);
+            say_err!("⚠️ '{}' is a file; {} files go to '{}' instead", file.display(), category, name);
+            opts.folder_names.insert(category, name);
+        }
+    }
+    if !opts.dry_run {
+        let (planned, needed) = scan_run(&folders, stdin_files.as_ref(), &opts);
+        if report_shortfalls(needed.shortfalls(), &opts) && !force {
+            say_err!("❌ Error: the files won't fit; free up space, or use --force to try anyway");
+            std::process::exit(1);
+        }
+        // Scripts have no one to press Enter, so only a terminal is asked
+        if !interactive && !yes && io::stdin().is_terminal() && !confirm_run(&planned, &opts) {
+            say!("Nothing was {}.", opts.past_tense());
+            return;
+        }
+    }
+
+    // The bar would garble JSON, piped output and the interactive prompts
+    let show_progress = progress
+        && !json
+        && !summary_json
+        && !emit_sh
+        && !interactive
+        && verbosity != Verbosity::Quiet
+        && io::stdout().is_terminal();
+    // A rollback changes what happened to files that were already reported, so then the reports wait for the end
+    let live_reports = !opts.transactional || opts.dry_run;
+    let reports = json || plan_out.is_some() || csv_out.is_some();
+    opts.keep_actions = keeps_actions(&opts, reports && live_reports, tree, emit_sh);
+    let json_report = json.then(|| report::JsonReport::start(BufWriter::new(io::stdout())));
+    let json_report = match json_report.transpose() {
+        Ok(json_report) => json_report,
+        Err(e) => {
+            say_err!("❌ Error: cannot write the JSON report: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let plan_writer = plan_out.map(|path| PlanWriter::create(Path::new(path), canonical, &opts));
+    let plan_writer = match plan_writer.transpose() {
+        Ok(plan_writer) => plan_writer.zip(plan_out.map(String::as_str)),
+        Err(e) => {
+            say_err!("❌ Error: cannot write plan '{}': {}", plan_out.unwrap(), e);
+            std::process::exit(1);
+        }
+    };
+    // Created only now, so a rejected command line leaves an existing file alone,
+    // but still before any file is touched
+    let csv_report =
+        csv_out.map(|path| File::create(path).and_then(|file| report::CsvReport::start(BufWriter::new(file))));
+    let csv_report = match csv_report.transpose() {
+        Ok(csv_report) => csv_report.zip(csv_out.map(String::as_str)),
+        Err(e) => {
+            say_err!("❌ Error: cannot write the CSV report '{}': {}", csv_out.unwrap(), e);
+            std::process::exit(1);
+        }
+    };
+    let mut reports = Reports { json: json_report, plan: plan_writer, csv: csv_report, error: None };
+    // What a dry run would need on each drive, for the warnings at the end
+    let mut space_needed = space::SpaceNeeds::new(opts.move_files);
+    // --limit is for all folders together
+    let limit = opts.limit;
+    let mut organized: Vec<(&Path, Summary)> = Vec::new();
+    for (i, (root, ignore)) in folders.iter().enumerate() {
+        opts.ignore = ignore.clone();
+        if !json && !summary_json && !emit_sh && verbosity != Verbosity::Quiet {
+            if i > 0 {
+                println!();
+            }
+            print_header(root, &opts, stdin_files.as_ref().map(Vec::len));
+        }
+
+        let bar = if show_progress {
+            let total = match &stdin_files {
+                Some(files) => Ok(files.len()),
+                None => count_files(root, &opts),
+            };
+            match total {
+                Ok(total) => Some(ProgressBar { total, last_draw: None }),
+                Err(e) => {
+                    say_err!("❌ Failed to read directory: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            None
+        };
+        // With the bar shown, per-file success lines are only listed in verbose mode
+        let list_verbosity = match (&bar, verbosity) {
+            (Some(_), Verbosity::Normal) => Verbosity::Quiet,
+            _ => verbosity,
+        };
+        // Shared by the progress callback and the per-file lines printed in between
+        let bar = Mutex::new(bar);
+
+        let confirm = |planned: &PlannedAction| if non_tty { Decision::Skip } else { prompter.ask(planned) };
+        let progress = |done: usize, current: &Path| {
+            if let Some(bar) = bar.lock().unwrap().as_mut() {
+                bar.update(done, current);
+            }
+        };
+        let print = |action: &FileAction| {
+            if live_reports {
+                reports.add(action);
+            }
+            if opts.dry_run {
+                space_needed.add(action);
+            }
+            // The tree and the script replace the per-file "Would copy" lines
+            if json || list_verbosity == Verbosity::Quiet || ((tree || emit_sh) && action.status.is_placed()) {
+                return;
+            }
+            if let Some(bar) = bar.lock().unwrap().as_ref() {
+                bar.clear();
+            }
+            print_action(action, &opts, list_verbosity);
+        };
+        let result = match stdin_files.take() {
+            Some(files) => organize_files_with_callbacks(files, &opts, confirm, progress, print),
+            None => organize_with_callbacks(root, &opts, confirm, progress, print),
+        };
+        if let Some(bar) = bar.into_inner().unwrap() {
+            bar.clear();
+        }
+        let summary = match result {
+            Ok(summary) => summary,
+            Err(e) => {
+                say_err!("❌ Failed to read directory: {}", e);
+                if folders.len() == 1 || opts.fail_fast {
+                    std::process::exit(1);
+                }
+                bad_folders += 1;
+                continue;
+            }
+        };
+
+        if !json {
+            if tree {
+                let out_root = match &opts.output {
+                    Some(output) => {
+                        fs::canonicalize(output).or_else(|_| std::path::absolute(output)).unwrap_or(output.clone())
                     }
+                    None => root.clone(),
+                };
+                say!("\n🌳 Planned layout of {}:", out_root.display());
+                for line in tree_lines(&summary.actions, &out_root) {
+                    println!("{}", line);
                 }
             }
+            if list_verbosity != Verbosity::Quiet {
+                for dir in &summary.removed_dirs {
+                    say!("🧹 Removed empty folder: '{}'", dir.display());
+                }
+            }
+        }
+
+        let stopped = summary.stopped;
+        if let Some(left) = &mut opts.limit {
+            *left = left.saturating_sub(summary.limit_used);
+        }
+        organized.push((root, summary));
+        if stopped {
+            break;
+        }
+    }
+    opts.limit = limit;
+
+    // A transactional run that failed also puts back what the folders before it moved
+    if opts.transactional
+        && !opts.dry_run
+        && let Some(((_, failed), earlier)) = organized.split_last_mut()
+        && !failed.failures_by_kind().is_empty()
+    {
+        for (root, summary) in earlier.iter_mut().rev() {
+            match roll_back(root, &opts, summary.clone()) {
+                Ok(rolled_back) => *summary = rolled_back,
+                Err(e) => say_err!("⚠️ Could not roll back '{}': {}", root.display(), e),
+            }
+        }
+    }
+    let folder_count = organized.len();
+    let mut organized = organized.into_iter().map(|(_, summary)| summary);
+    let Some(mut summary) = organized.next() else {
+        std::process::exit(1);
+    };
+    organized.for_each(|other| summary.merge(other));
+
+    if !live_reports {
+        summary.actions.iter().for_each(|action| reports.add(action));
+    }
+    if let Some(error) = &reports.error {
+        say_err!("❌ Error: {}", error);
+        std::process::exit(1);
+    }
+    if let Some((plan, plan_path)) = reports.plan.take()
+        && let Err(e) = plan.finish()
+    {
+        say_err!("❌ Error: cannot write plan '{}': {}", plan_path, e);
+        std::process::exit(1);
+    }
+
+    if let Some((csv_report, csv_path)) = reports.csv.take()
+        && let Err(e) = csv_report.finish()
+    {
+        say_err!("❌ Error: cannot write the CSV report '{}': {}", csv_path, e);
+        std::process::exit(1);
+    }
+
+    // Failing to archive the summary is reported but doesn't fail the run
+    let summary_text = summary_lines(&summary, &opts);
+    let roots: Vec<&Path> = folders.iter().map(|(root, _)| root.as_path()).collect();
+    let summary_written = match &summary_out {
+        Some(out) => match write_summary_file(out, &roots, &opts, &summary, &summary_text) {
+            Ok(()) => true,
             Err(e) => {
-                eprintln!("⚠️ Failed to read an entry: {}", e);
-                *counters.get_mut("Errors").unwrap() += 1;
+                say_err!("⚠️ Could not write summary to '{}': {}", out.display(), e);
+                false
             }
+        },
+        None => false,
+    };
+
+    if let Some(json_report) = reports.json.take() {
+        if let Err(e) = json_report.finish(canonical, &opts, &summary) {
+            say_err!("❌ Error: cannot write the JSON report: {}", e);
+            std::process::exit(1);
+        }
+        if summary.count("Errors") > 0 {
+            std::process::exit(EXIT_FILE_ERRORS);
+        }
+        return;
+    }
+    if summary_json {
+        print_problems(&summary.actions, verb);
+        println!("{}", report::summary_json(&summary));
+        if summary.count("Errors") > 0 {
+            std::process::exit(EXIT_FILE_ERRORS);
+        }
+        return;
+    }
+
+    if emit_sh {
+        print_problems(&summary.actions, verb);
+        print!("{}", script::shell_script(&summary.actions, &opts));
+        if summary.count("Errors") > 0 {
+            std::process::exit(EXIT_FILE_ERRORS);
         }
+        return;
     }
 
-    // Summary
-    println!("\n📊 Summary:");
-    for key in &["Images", "Documents", "Videos", "Audio", "Archives", "Code", "Others", "Errors"] {
-        let count = counters.get(*key).cloned().unwrap_or_default();
-        println!("  - {:<9} : {}", key, count);
+    print_problems(&summary.actions, verb);
+    if opts.dry_run {
+        report_shortfalls(space_needed.shortfalls(), &opts);
     }
 
-    println!("\n🎉 Done! (Safe Mode copy completed.)");
+    if verbosity != Verbosity::Quiet {
+        if folder_count > 1 {
+            say!("\n📊 Summary of {} folders:", folder_count);
+        } else {
+            say!("\n📊 Summary:");
+        }
+    }
+    for line in &summary_text {
+        println!("{}", line);
+    }
+    if timing || verbosity == Verbosity::Verbose {
+        say!("{}", timing_line(&summary, opts.dry_run));
+    }
+    if let Some(out) = &summary_out
+        && summary_written
+        && verbosity != Verbosity::Quiet
+    {
+        say!("📝 Summary written to {}", out.display());
+    }
+
+    let errors = summary.count("Errors");
+    if summary.stopped && opts.transactional && errors > 0 && opts.dry_run {
+        say!("\n🛑 Stopped at the first error (--transactional); a real run would put back what it moved.");
+    } else if summary.stopped && opts.transactional && errors > 0 {
+        let rolled_back = summary.count("Rolled back");
+        let files = if rolled_back == 1 { "file was" } else { "files were" };
+        say!("\n↩️ Stopped at the first error (--transactional); {} moved {} put back.", rolled_back, files);
+    } else if summary.stopped && opts.fail_fast && errors > 0 {
+        say!("\n🛑 Stopped at the first error (--fail-fast); the summary above covers the files handled so far.");
+    } else if let (true, Some(limit)) = (summary.stopped, opts.limit.filter(|&l| summary.limit_used >= l)) {
+        say!("\n🛑 Stopped after {} files (--limit); the summary above covers those.", limit);
+    } else if summary.stopped {
+        say!("\n🛑 Stopped early; the summary above covers the files handled so far.");
+    } else if let Some(plan_path) = plan_out {
+        say!("\n📋 Plan written to {}; run with --apply {} to carry it out.", plan_path, plan_path);
+    } else if verbosity == Verbosity::Quiet {
+        // No "Done" line
+    } else if opts.dry_run {
+        say!("\n🎉 Done! (Dry run, nothing was changed.)");
+    } else if opts.move_files {
+        say!("\n🎉 Done! (Move completed.)");
+    } else if opts.link.is_some() {
+        say!("\n🎉 Done! (Links created, originals left in place.)");
+    } else {
+        say!("\n🎉 Done! (Safe Mode copy completed.)");
+    }
+    if bad_folders > 0 {
+        let folders = if bad_folders == 1 { "folder was" } else { "folders were" };
+        say_err!("⚠️ {} {} left out because of the errors above.", bad_folders, folders);
+    }
+    if errors > 0 || bad_folders > 0 {
+        std::process::exit(EXIT_FILE_ERRORS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_writing_only_reports_dont_keep_actions() {
+        let opts = Options::default();
+        // A CSV-only run
+        assert!(!keeps_actions(&opts, true, false, false));
+        assert!(keeps_actions(&opts, true, true, false));
+        assert!(keeps_actions(&opts, true, false, true));
+        assert!(keeps_actions(&Options { diff: true, ..Options::default() }, true, false, false));
+        // No reports, or ones that wait for a rollback
+        assert!(keeps_actions(&opts, false, false, false));
+    }
 }
@@ -0,0 +1,103 @@
+//! `--preserve all`: gives copies the owner, group and extended attributes
+//! of their source, on top of the permission bits every copy keeps and the
+//! timestamps of `--preserve-times`.
+//!
+//! What can be kept depends on the platform and who runs the tool:
+//!
+//! - Linux: owner and group, and extended attributes, which also carry
+//!   POSIX ACLs (`system.posix_acl_*`) and file capabilities. Only root may
+//!   give a file another owner, or set `trusted.*` and `security.*`
+//!   attributes (SELinux labels); others can still keep a group they are in.
+//! - Other Unix systems (macOS, the BSDs): owner and group as on Linux;
+//!   extended attributes aren't copied.
+//! - Windows: nothing beyond what every copy keeps (the read-only flag).
+//!
+//! What can't be kept is reported with [`io::ErrorKind::PermissionDenied`]
+//! or [`io::ErrorKind::Unsupported`], so a run can say so once and go on.
+
+use std::io;
+use std::path::Path;
+
+/// Gives `dest` the owner and group of `src`. Without the right to change
+/// the owner, the group is still set if that's allowed, and the missing
+/// permission is reported.
+#[cfg(unix)]
+pub fn copy_owner(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{MetadataExt, chown};
+
+    let (src_meta, dest_meta) = (std::fs::metadata(src)?, std::fs::metadata(dest)?);
+    let (uid, gid) = (src_meta.uid(), src_meta.gid());
+    if (uid, gid) == (dest_meta.uid(), dest_meta.gid()) {
+        return Ok(());
+    }
+    match chown(dest, Some(uid), Some(gid)) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            if gid != dest_meta.gid() {
+                chown(dest, None, Some(gid))?;
+            }
+            if uid != dest_meta.uid() { Err(e) } else { Ok(()) }
+        }
+        result => result,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn copy_owner(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "file owners can't be kept on this platform"))
+}
+
+/// Copies every extended attribute of `src` onto `dest`. All of them are
+/// tried; the first that couldn't be set is reported.
+#[cfg(target_os = "linux")]
+pub fn copy_xattrs(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = |path: &Path| CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other);
+    let (src, dest) = (c_path(src)?, c_path(dest)?);
+    // SAFETY: `src` is a valid C string and the buffer is as large as passed
+    let names = read_sized(|buf, size| unsafe { libc::listxattr(src.as_ptr(), buf.cast(), size) })?;
+    let mut first_error = None;
+    for name in names.split(|&b| b == 0).filter(|name| !name.is_empty()) {
+        let name = CString::new(name).map_err(io::Error::other)?;
+        // SAFETY: as above, with `name` a valid C string too
+        let set = read_sized(|buf, size| unsafe { libc::getxattr(src.as_ptr(), name.as_ptr(), buf.cast(), size) })
+            .and_then(|value| {
+                // SAFETY: both strings are valid and `value` holds `value.len()` bytes
+                let status =
+                    unsafe { libc::setxattr(dest.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0) };
+                if status == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+            });
+        if let Err(e) = set {
+            first_error.get_or_insert_with(|| io::Error::new(e.kind(), format!("{}: {}", name.to_string_lossy(), e)));
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn copy_xattrs(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "extended attributes can't be copied on this platform"))
+}
+
+/// Calls a `listxattr`-style `read(buffer, size)` first to learn the size
+/// and then to fill a buffer of that size, again if it grew in between.
+#[cfg(target_os = "linux")]
+fn read_sized(mut read: impl FnMut(*mut u8, usize) -> libc::ssize_t) -> io::Result<Vec<u8>> {
+    loop {
+        let size = read(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0u8; size as usize];
+        let read_len = read(buf.as_mut_ptr(), buf.len());
+        if read_len >= 0 {
+            buf.truncate(read_len as usize);
+            return Ok(buf);
+        }
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ERANGE) {
+            return Err(e);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Append-only operation log (`--log FILE`), one JSON object per line:
+//!
+//! ```text
+//! {"timestamp":"2024-06-01T12:00:00+02:00","action":"copy","source":"/in/a.jpg","destination":"/in/Images/a.jpg"}
+//! ```
+//!
+//! Only real copies and moves are logged, never dry runs. The log is the
+//! input for `--undo`.
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// One line of the operation log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// When the operation finished, RFC 3339 in local time.
+    pub timestamp: String,
+    /// "copy", "move" or "link".
+    pub action: String,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+}
+
+impl LogEntry {
+    /// Parsed `timestamp`, if it is valid RFC 3339.
+    pub fn time(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.timestamp).ok()
+    }
+}
+
+/// An open log file that entries are appended to.
+#[derive(Debug)]
+pub struct OperationLog {
+    file: File,
+}
+
+impl OperationLog {
+    /// Opens (creating if needed) `path` for appending.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(OperationLog { file })
+    }
+
+    /// Appends a record for a completed copy or move and flushes it, so the
+    /// log stays accurate even if the run is interrupted.
+    pub fn append(&mut self, action: &str, source: &Path, destination: &Path) -> io::Result<()> {
+        let entry = LogEntry {
+            timestamp: Local::now().to_rfc3339(),
+            action: action.to_string(),
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+        };
+        let line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()
+    }
+}
+
+/// Reads every entry from a log file, in the order they were written.
+/// Blank lines are ignored; a malformed line is an error.
+pub fn read_log(path: &Path) -> io::Result<Vec<LogEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e))
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
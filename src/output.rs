@@ -0,0 +1,154 @@
+//! `--color`: lines starting with one of the tool's emojis are colored by
+//! what they report (green for success, red for errors, ...), and with
+//! `--color never` printed as plain text without the emoji.
+
+use colored::{Color, Colorize};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When to use colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only on terminals, unless `NO_COLOR` is set.
+    Auto,
+    Always,
+    /// No colors and no emojis, for logs and CI.
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("invalid --color '{}' (expected auto, always or never)", s)),
+        }
+    }
+}
+
+static COLOR_STDOUT: AtomicBool = AtomicBool::new(false);
+static COLOR_STDERR: AtomicBool = AtomicBool::new(false);
+static EMOJI: AtomicBool = AtomicBool::new(true);
+static SAY_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Applies `choice` to everything printed from now on.
+pub fn init(choice: ColorChoice) {
+    let (stdout, stderr) = match choice {
+        ColorChoice::Auto => {
+            let allowed = env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+            (allowed && io::stdout().is_terminal(), allowed && io::stderr().is_terminal())
+        }
+        ColorChoice::Always => (true, true),
+        ColorChoice::Never => (false, false),
+    };
+    // Whether to color is decided here, per stream
+    colored::control::set_override(true);
+    COLOR_STDOUT.store(stdout, Ordering::Relaxed);
+    COLOR_STDERR.store(stderr, Ordering::Relaxed);
+    EMOJI.store(choice != ColorChoice::Never, Ordering::Relaxed);
+}
+
+/// Sends everything printed with [`say`] to stderr from now on, keeping
+/// stdout for machine-readable output (`--summary-json`).
+pub fn say_to_stderr() {
+    SAY_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Whether [`say`] prints to stderr.
+pub fn says_to_stderr() -> bool {
+    SAY_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// The emojis lines start with and the color such a line gets.
+const MARKERS: &[(&str, Option<Color>)] = &[
+    ("✅", Some(Color::Green)),
+    ("🎉", Some(Color::Green)),
+    ("↩️", Some(Color::Green)),
+    ("❌", Some(Color::Red)),
+    ("🛑", Some(Color::Red)),
+    ("⚠️", Some(Color::Yellow)),
+    ("❗", Some(Color::Yellow)),
+    ("❓", Some(Color::Yellow)),
+    ("➡️", Some(Color::Cyan)),
+    ("🔎", Some(Color::Cyan)),
+    ("♻️", Some(Color::Blue)),
+    ("⏭️", Some(Color::BrightBlack)),
+    ("🔍", Some(Color::BrightBlack)),
+    ("⏪", None),
+    ("🏋️", None),
+    ("📁", None),
+    ("📂", None),
+    ("📅", None),
+    ("📊", None),
+    ("📋", None),
+    ("📝", None),
+    ("📥", None),
+    ("🔁", None),
+    ("🗑️", None),
+    ("🚚", None),
+    ("🔗", None),
+    ("🧹", None),
+    ("🌳", None),
+    ("⏱️", None),
+    ("🕒", None),
+];
+
+/// `text` as it should be printed to stdout (or stderr with `to_stderr`).
+pub fn style(text: &str, to_stderr: bool) -> String {
+    let color = if to_stderr { &COLOR_STDERR } else { &COLOR_STDOUT }.load(Ordering::Relaxed);
+    let emoji = EMOJI.load(Ordering::Relaxed);
+    text.split('\n').map(|line| style_line(line, color, emoji)).collect::<Vec<_>>().join("\n")
+}
+
+fn style_line(line: &str, color: bool, emoji: bool) -> String {
+    let body = line.trim_start();
+    let indent = &line[..line.len() - body.len()];
+    let Some(&(marker, line_color)) = MARKERS.iter().find(|(marker, _)| body.starts_with(marker)) else {
+        return line.to_string();
+    };
+    let body = if emoji { body.to_string() } else { body[marker.len()..].trim_start().to_string() };
+    match line_color {
+        Some(c) if color => format!("{}{}", indent, body.color(c)),
+        _ => format!("{}{}", indent, body),
+    }
+}
+
+/// `println!` for the tool's messages, styled for `--color` (`eprintln!`
+/// after [`say_to_stderr`]).
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::says_to_stderr() {
+            eprintln!("{}", $crate::output::style(&format!($($arg)*), true))
+        } else {
+            println!("{}", $crate::output::style(&format!($($arg)*), false))
+        }
+    };
+}
+
+/// `eprintln!` for the tool's messages, styled for `--color`.
+macro_rules! say_err {
+    ($($arg:tt)*) => {
+        eprintln!("{}", $crate::output::style(&format!($($arg)*), true))
+    };
+}
+
+pub(crate) use {say, say_err};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_drops_the_emoji_and_keeps_the_rest() {
+        assert_eq!(style_line("✅ Copied: 'a.jpg'", false, false), "Copied: 'a.jpg'");
+        assert_eq!(style_line("   🔍 extension 'jpg'", false, false), "   extension 'jpg'");
+        assert_eq!(style_line("⚠️ careful", false, true), "⚠️ careful");
+        assert_eq!(style_line("  - Images : 1", false, false), "  - Images : 1");
+        assert_eq!(style_line("", false, false), "");
+    }
+}
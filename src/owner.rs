@@ -0,0 +1,76 @@
+//! `--by-owner`: sorts files into a folder per owning user first
+//! (`alice/Images/...`), for cleaning up shared folders.
+//!
+//! User names come from `/etc/passwd`, read once per run. A uid that isn't
+//! listed there (e.g. a user known only to LDAP) is used as the folder name.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// Folder for files whose owner couldn't be read.
+pub const UNKNOWN_OWNER_FOLDER: &str = "Unknown-Owner";
+
+/// Uid -> user name.
+#[derive(Debug, Clone, Default)]
+pub struct Owners {
+    names: HashMap<u32, String>,
+}
+
+impl Owners {
+    /// Reads the user names from `/etc/passwd`. Without one every owner is
+    /// named by uid.
+    pub fn load() -> Self {
+        let text = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+        Owners { names: parse_passwd(&text) }
+    }
+
+    /// Name of the folder for the owner of `path`.
+    #[cfg(unix)]
+    pub fn folder(&self, path: &Path) -> io::Result<String> {
+        use std::os::unix::fs::MetadataExt;
+
+        let uid = std::fs::metadata(path)?.uid();
+        Ok(self.names.get(&uid).cloned().unwrap_or_else(|| uid.to_string()))
+    }
+
+    #[cfg(not(unix))]
+    pub fn folder(&self, _path: &Path) -> io::Result<String> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "file owners are only available on Unix"))
+    }
+}
+
+/// `name:password:uid:...` lines to uid -> name; the first name for a uid wins.
+fn parse_passwd(text: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for line in text.lines().filter(|line| !line.starts_with('#')) {
+        let mut fields = line.split(':');
+        let (Some(name), Some(_), Some(uid)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        // A name that can't be a single folder isn't used
+        if let Ok(uid) = uid.parse()
+            && !name.is_empty()
+            && !name.contains(['/', '\\'])
+            && name != "."
+            && name != ".."
+        {
+            names.entry(uid).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwd_lines_map_uids_to_names() {
+        let names = parse_passwd("root:x:0:0:root:/root:/bin/sh\n# comment\nalice:x:1000:1000::/home/alice:/bin/sh\n\
+             toor:x:0:0::/root:/bin/sh\nbroken\n");
+        assert_eq!(names.get(&0).map(String::as_str), Some("root"));
+        assert_eq!(names.get(&1000).map(String::as_str), Some("alice"));
+        assert_eq!(names.len(), 2);
+    }
+}
@@ -0,0 +1,249 @@
+//! `--plan` / `--apply`: a dry run can write the exact set of copies or moves
+//! it would make to a JSON file, which a later run executes as-is.
+//!
+//! Destinations are stored after name conflicts were resolved, so applying a
+//! plan never picks different names than the ones that were reviewed.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::oplog::OperationLog;
+use crate::transfer::{copy_new, move_new};
+use crate::{FileAction, Options};
+
+/// A reviewed set of file operations.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    /// When the plan was made, RFC 3339 in local time.
+    pub created: String,
+    /// Folder that was organized.
+    pub folder: PathBuf,
+    /// "copy" or "move".
+    pub action: String,
+    pub entries: Vec<PlanEntry>,
+}
+
+/// One planned copy or move, with what the source looked like at the time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub category: Option<String>,
+    /// Size of the source in bytes when the plan was made.
+    pub size: u64,
+    /// Modified time of the source when the plan was made, RFC 3339.
+    pub modified: Option<String>,
+}
+
+impl Plan {
+    /// Reads a plan written by a [`PlanWriter`].
+    pub fn read(path: &Path) -> io::Result<Plan> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Writes a [`Plan`] as pretty-printed JSON while the dry run goes on, one
+/// entry per would-be copy or move as it's found, so the plan of a huge
+/// folder is never held in memory.
+pub struct PlanWriter {
+    out: io::BufWriter<fs::File>,
+    entries: usize,
+}
+
+impl PlanWriter {
+    /// Creates the plan file at `path` for organizing `root` with `options`.
+    pub fn create(path: &Path, root: &Path, options: &Options) -> io::Result<PlanWriter> {
+        let mut out = io::BufWriter::new(fs::File::create(path)?);
+        writeln!(out, "{{\n  \"created\": {},", serde_json::to_string(&Local::now().to_rfc3339())?)?;
+        writeln!(out, "  \"folder\": {},", serde_json::to_string(root)?)?;
+        writeln!(out, "  \"action\": {},", serde_json::to_string(options.verb())?)?;
+        write!(out, "  \"entries\": [")?;
+        Ok(PlanWriter { out, entries: 0 })
+    }
+
+    /// Adds the file `action` would place; other actions aren't part of a plan.
+    pub fn add(&mut self, action: &FileAction) -> io::Result<()> {
+        let Some(destination) = &action.destination else { return Ok(()) };
+        if !action.status.is_placed() {
+            return Ok(());
+        }
+        let meta = fs::metadata(&action.source)?;
+        let entry = PlanEntry {
+            source: action.source.clone(),
+            destination: destination.clone(),
+            category: action.category.clone(),
+            size: meta.len(),
+            modified: meta.modified().ok().map(rfc3339),
+        };
+        // Indented to sit inside "entries"; line breaks in strings are escaped, so this only touches the layout
+        let entry = serde_json::to_string_pretty(&entry)?.replace('\n', "\n    ");
+        write!(self.out, "{}\n    {}", if self.entries > 0 { "," } else { "" }, entry)?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Ends the plan.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.write_all(if self.entries > 0 { b"\n  ]\n}\n" } else { b"]\n}\n" })?;
+        self.out.flush()
+    }
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).to_rfc3339()
+}
+
+/// Result of applying one plan entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyStatus {
+    Copied,
+    Moved,
+    /// Would be copied/moved (dry run).
+    WouldApply,
+    /// Left alone because things changed since the plan was made; the string says why.
+    Skipped(String),
+    /// Something went wrong; the string is the error message.
+    Failed(String),
+}
+
+/// One applied (or skipped) plan entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyAction {
+    pub entry: PlanEntry,
+    pub status: ApplyStatus,
+    /// Drift that didn't stop the entry from being applied, e.g. an edited source.
+    pub warning: Option<String>,
+}
+
+/// Everything an apply run did, in plan order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplySummary {
+    pub actions: Vec<ApplyAction>,
+}
+
+impl ApplySummary {
+    /// Number of entries that were (or would be) applied.
+    pub fn applied(&self) -> usize {
+        self.count(|s| matches!(s, ApplyStatus::Copied | ApplyStatus::Moved | ApplyStatus::WouldApply))
+    }
+
+    /// Number of entries left alone because of drift.
+    pub fn skipped(&self) -> usize {
+        self.count(|s| matches!(s, ApplyStatus::Skipped(_)))
+    }
+
+    /// Number of entries that failed.
+    pub fn failed(&self) -> usize {
+        self.count(|s| matches!(s, ApplyStatus::Failed(_)))
+    }
+
+    fn count(&self, pred: impl Fn(&ApplyStatus) -> bool) -> usize {
+        self.actions.iter().filter(|a| pred(&a.status)).count()
+    }
+}
+
+/// Executes `plan` exactly: every source goes to its planned destination.
+/// Entries whose source is gone or whose destination is now taken are
+/// skipped instead of improvising a new name; sources that changed since
+/// the plan was made are still applied, with a warning. Completed
+/// operations are appended to `log` so they can be undone.
+pub fn apply_plan(plan: &Plan, dry_run: bool, mut log: Option<&mut OperationLog>) -> ApplySummary {
+    let moving = plan.action == "move";
+    let mut summary = ApplySummary::default();
+    for entry in &plan.entries {
+        let mut warning = None;
+        let status = match apply_entry(entry, moving, dry_run, &mut warning) {
+            Ok(status) => status,
+            Err(e) => ApplyStatus::Failed(e.to_string()),
+        };
+        if let (Some(log), ApplyStatus::Copied | ApplyStatus::Moved) = (log.as_deref_mut(), &status)
+            && let Err(e) = log.append(&plan.action, &entry.source, &entry.destination)
+        {
+            warning = Some(format!("could not write to log: {}", e));
+        }
+        summary.actions.push(ApplyAction {
+            entry: entry.clone(),
+            status,
+            warning,
+        });
+    }
+    summary
+}
+
+fn apply_entry(
+    entry: &PlanEntry,
+    moving: bool,
+    dry_run: bool,
+    warning: &mut Option<String>,
+) -> io::Result<ApplyStatus> {
+    let skip = |reason: &str| Ok(ApplyStatus::Skipped(reason.to_string()));
+
+    let meta = match fs::metadata(&entry.source) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return skip("source no longer exists"),
+        Err(e) => return Err(e),
+    };
+    if entry.destination.exists() {
+        return skip("destination is already taken");
+    }
+    let modified = meta.modified().ok().map(rfc3339);
+    if meta.len() != entry.size || (entry.modified.is_some() && modified != entry.modified) {
+        *warning = Some("source changed since the plan was made".to_string());
+    }
+    if dry_run {
+        return Ok(ApplyStatus::WouldApply);
+    }
+
+    if let Some(parent) = entry.destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let placed = if moving {
+        move_new(&entry.source, &entry.destination).map(|()| ApplyStatus::Moved)
+    } else {
+        copy_new(&entry.source, &entry.destination).map(|()| ApplyStatus::Copied)
+    };
+    match placed {
+        // Taken since the check above
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => skip("destination is already taken"),
+        placed => placed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ActionStatus;
+    use crate::action::SkipReason;
+
+    #[test]
+    fn written_plans_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("cat.jpg");
+        fs::write(&source, b"meow").unwrap();
+        let placed = FileAction {
+            destination: Some(dir.path().join("Images/cat.jpg")),
+            category: Some("Images".to_string()),
+            status: ActionStatus::WouldCopy,
+            ..FileAction::skipped(source.clone(), SkipReason::Hidden)
+        };
+        let path = dir.path().join("plan.json");
+        let mut writer = PlanWriter::create(&path, dir.path(), &Options::default()).unwrap();
+        writer.add(&placed).unwrap();
+        writer.add(&FileAction::skipped(dir.path().join(".hidden"), SkipReason::Hidden)).unwrap();
+        writer.finish().unwrap();
+
+        let plan = Plan::read(&path).unwrap();
+        assert_eq!((plan.folder.as_path(), plan.action.as_str()), (dir.path(), "copy"));
+        assert_eq!(plan.entries.len(), 1);
+        assert_eq!((&plan.entries[0].source, plan.entries[0].size), (&source, 4));
+        assert_eq!(plan.entries[0].destination, dir.path().join("Images/cat.jpg"));
+
+        PlanWriter::create(&path, dir.path(), &Options::default()).unwrap().finish().unwrap();
+        assert!(Plan::read(&path).unwrap().entries.is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+//! `--by-prefix`: files whose names start with the same token, like
+//! `IMG_0001.jpg` and `IMG_0002.jpg`, are grouped into a folder named after
+//! it (`Images/IMG/`) once enough of them share it.
+
+use std::collections::{HashMap, HashSet};
+
+/// Default `--prefix-min`: how many files must share a prefix to get a folder.
+pub const DEFAULT_PREFIX_MIN: usize = 5;
+
+/// The leading token of `name`, up to the first `_`, `-` or space, e.g.
+/// `invoice` for `invoice_2023_04.pdf`. Names without such a separator
+/// before the extension, or starting with one or a `.`, have no prefix.
+pub fn name_prefix(name: &str) -> Option<&str> {
+    if name.starts_with('.') {
+        return None;
+    }
+    let stem = name.split('.').next().unwrap_or(name);
+    let end = stem.find(['_', '-', ' '])?;
+    Some(&name[..end]).filter(|prefix| !prefix.is_empty())
+}
+
+/// The prefixes shared by at least `min` of `names`.
+pub fn common_prefixes<'a>(names: impl IntoIterator<Item = &'a str>, min: usize) -> HashSet<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for prefix in names.into_iter().filter_map(name_prefix) {
+        *counts.entry(prefix).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .filter(|&(_, count)| count >= min)
+        .map(|(prefix, _)| prefix.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_prefixes_shared_by_enough_files() {
+        assert_eq!(name_prefix("IMG_0001.jpg"), Some("IMG"));
+        assert_eq!(name_prefix("invoice-2023-04.pdf"), Some("invoice"));
+        assert_eq!(name_prefix("Screen Shot 1.png"), Some("Screen"));
+        assert_eq!(name_prefix("report.final_v2.pdf"), None);
+        assert_eq!(name_prefix("_draft.txt"), None);
+        assert_eq!(name_prefix(".config_backup"), None);
+
+        let names = ["IMG_1.jpg", "IMG_2.jpg", "IMG_3.jpg", "scan_1.pdf", "scan_2.pdf", "notes.txt"];
+        let prefixes = common_prefixes(names, 3);
+        assert_eq!(prefixes, HashSet::from(["IMG".to_string()]));
+        assert_eq!(common_prefixes(names, 2).len(), 2);
+    }
+}
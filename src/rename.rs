@@ -0,0 +1,76 @@
+//! `--rename`: tidies up file names as files are placed, e.g.
+//! `My Photo (1).JPG` becomes `my_photo_1.jpg` with
+//! `--rename lowercase,spaces-to-underscore,strip-special`.
+//!
+//! The new name is what the collision check sees, so two files that end up
+//! with the same name are told apart like any other name clash.
+
+use std::str::FromStr;
+
+/// One way of rewriting a file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameRule {
+    /// `Report.PDF` -> `report.pdf`.
+    Lowercase,
+    /// `my file.txt` -> `my_file.txt`.
+    SpacesToUnderscore,
+    /// Drops everything but letters, digits, spaces, `-`, `_` and `.`.
+    StripSpecial,
+}
+
+impl FromStr for RenameRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lowercase" => Ok(RenameRule::Lowercase),
+            "spaces-to-underscore" => Ok(RenameRule::SpacesToUnderscore),
+            "strip-special" => Ok(RenameRule::StripSpecial),
+            _ => Err(format!(
+                "unknown --rename rule '{}' (expected lowercase, spaces-to-underscore or strip-special)",
+                s
+            )),
+        }
+    }
+}
+
+/// Parses a comma-separated list of rules, like `lowercase,strip-special`.
+pub fn parse_rules(list: &str) -> Result<Vec<RenameRule>, String> {
+    list.split(',').map(|rule| rule.trim().parse()).collect()
+}
+
+/// `name` with `rules` applied in order. A name that would end up empty or
+/// turn into a hidden file (`#1.txt` -> `1.txt` is fine, `#.txt` -> `.txt`
+/// isn't) is kept as it was.
+pub fn apply_rules(name: &str, rules: &[RenameRule]) -> String {
+    let mut renamed = name.to_string();
+    for rule in rules {
+        renamed = match rule {
+            RenameRule::Lowercase => renamed.to_lowercase(),
+            RenameRule::SpacesToUnderscore => renamed.replace(' ', "_"),
+            RenameRule::StripSpecial => renamed
+                .chars()
+                .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'))
+                .collect(),
+        };
+    }
+    if renamed.is_empty() || (renamed.starts_with('.') && !name.starts_with('.')) {
+        return name.to_string();
+    }
+    renamed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_apply_in_order() {
+        let rules = parse_rules("lowercase,spaces-to-underscore,strip-special").unwrap();
+        assert_eq!(apply_rules("My Photo (1).JPG", &rules), "my_photo_1.jpg");
+        assert_eq!(apply_rules("Résumé 2024.PDF", &rules), "résumé_2024.pdf");
+        assert_eq!(apply_rules("#.txt", &rules), "#.txt");
+        assert_eq!(apply_rules("???", &rules), "???");
+        assert!(parse_rules("lowercase,shout").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use serde_json::{json, Map, Value};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::stats::FolderStats;
+use crate::{ActionStatus, FileAction, Options, Summary};
+
+impl ActionStatus {
+    /// Stable snake_case name used in machine-readable output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionStatus::Copied => "copied",
+            ActionStatus::Moved => "moved",
+            ActionStatus::WouldCopy => "would_copy",
+            ActionStatus::WouldMove => "would_move",
+            ActionStatus::Duplicate(_) => "duplicate",
+            ActionStatus::Skipped(_) => "skipped",
+            ActionStatus::Failed(_) => "failed",
+        }
+    }
+}
+
+fn action_to_json(action: &FileAction) -> Value {
+    let mut obj = Map::new();
+    obj.insert("source".into(), json!(action.source.display().to_string()));
+    obj.insert(
+        "destination".into(),
+        json!(action.destination.as_ref().map(|d| d.display().to_string())),
+    );
+    obj.insert("category".into(), json!(action.category));
+    obj.insert("status".into(), json!(action.status.as_str()));
+    if let Some(subcategory) = &action.subcategory {
+        obj.insert("subcategory".into(), json!(subcategory));
+    }
+    if let Some(content_type) = &action.content_type {
+        obj.insert("content_type".into(), json!(content_type));
+    }
+    match &action.status {
+        ActionStatus::Duplicate(original) => {
+            obj.insert("duplicate_of".into(), json!(original.display().to_string()));
+        }
+        ActionStatus::Skipped(reason) => {
+            obj.insert("reason".into(), json!(reason.to_string()));
+        }
+        ActionStatus::Failed(failure) => {
+            obj.insert("error".into(), json!(failure.message));
+            obj.insert("error_kind".into(), json!(failure.kind.as_str()));
+        }
+        _ => {}
+    }
+    if let Some(conflict) = action.conflict {
+        obj.insert("conflict".into(), json!(conflict.as_str()));
+    }
+    if let Some(change) = action.change {
+        obj.insert("change".into(), json!(change.as_str()));
+    }
+    if let Some(warning) = &action.warning {
+        obj.insert("warning".into(), json!(warning));
+    }
+    Value::Object(obj)
+}
+
+/// Builds the `--json` report: run settings, per-category counts and every action.
+pub fn json_report(root: &Path, options: &Options, summary: &Summary) -> Value {
+    let mut report = report_fields(root, options, summary);
+    report.insert("actions".into(), summary.actions.iter().map(action_to_json).collect());
+    Value::Object(report)
+}
+
+/// Writes the [`json_report`] to `out` while the run goes on: each action
+/// is added as its file is handled, and the counts follow once the run is
+/// over. Only the action being written is held in memory.
+pub struct JsonReport<W: Write> {
+    out: W,
+    actions: usize,
+}
+
+impl<W: Write> JsonReport<W> {
+    /// Opens the report. "actions" comes first (as in the sorted keys of
+    /// [`json_report`]), since the counts aren't known yet.
+    pub fn start(mut out: W) -> io::Result<Self> {
+        out.write_all(b"{\"actions\":[")?;
+        Ok(JsonReport { out, actions: 0 })
+    }
+
+    /// Writes the next element of "actions".
+    pub fn add(&mut self, action: &FileAction) -> io::Result<()> {
+        if self.actions > 0 {
+            self.out.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.out, &action_to_json(action))?;
+        self.actions += 1;
+        Ok(())
+    }
+
+    /// Ends the report with the settings and counts of the run.
+    pub fn finish(mut self, root: &Path, options: &Options, summary: &Summary) -> io::Result<()> {
+        self.out.write_all(b"]")?;
+        for (key, value) in report_fields(root, options, summary) {
+            self.out.write_all(b",")?;
+            serde_json::to_writer(&mut self.out, &key)?;
+            self.out.write_all(b":")?;
+            serde_json::to_writer(&mut self.out, &value)?;
+        }
+        self.out.write_all(b"}\n")?;
+        self.out.flush()
+    }
+}
+
+/// Writes the `--csv` report to `out` while the run goes on: a header, then
+/// a row for each file as it's handled, with its source, destination,
+/// category, size in bytes and status (as in the JSON report). Folders that
+/// were passed over get no row.
+pub struct CsvReport<W: Write> {
+    out: W,
+}
+
+impl<W: Write> CsvReport<W> {
+    /// Writes the header.
+    pub fn start(mut out: W) -> io::Result<Self> {
+        writeln!(out, "source,destination,category,size,status")?;
+        Ok(CsvReport { out })
+    }
+
+    /// Writes the row of `action`.
+    pub fn add(&mut self, action: &FileAction) -> io::Result<()> {
+        if matches!(&action.status, ActionStatus::Skipped(reason) if reason.is_folder()) {
+            return Ok(());
+        }
+        let destination = action.destination.as_ref().map(|d| d.display().to_string()).unwrap_or_default();
+        writeln!(
+            self.out,
+            "{},{},{},{},{}",
+            csv_field(&action.source.display().to_string()),
+            csv_field(&destination),
+            csv_field(action.category.as_deref().unwrap_or("")),
+            action.size.map(|size| size.to_string()).unwrap_or_default(),
+            action.status.as_str()
+        )
+    }
+
+    /// Flushes the rows still buffered.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// `value` quoted for CSV if it has to be: when it holds a comma, a quote
+/// or a line break. Quotes inside are doubled.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Everything in the `--json` report but the actions.
+fn report_fields(root: &Path, options: &Options, summary: &Summary) -> Map<String, Value> {
+    let (counts, bytes) = count_maps(summary);
+    let errors_by_kind: Map<String, Value> = summary
+        .failures_by_kind()
+        .into_iter()
+        .map(|(kind, n)| (kind.as_str().to_string(), json!(n)))
+        .collect();
+    let mut fields = Map::new();
+    fields.insert("folder".into(), json!(root.display().to_string()));
+    fields.insert("dry_run".into(), json!(options.dry_run));
+    fields.insert("mode".into(), json!(options.verb()));
+    fields.insert("counts".into(), Value::Object(counts));
+    fields.insert("total".into(), json!(summary.total_files()));
+    fields.insert("bytes".into(), Value::Object(bytes));
+    fields.insert("total_bytes".into(), json!(summary.total_bytes()));
+    fields.insert("errors_by_kind".into(), Value::Object(errors_by_kind));
+    fields.insert("elapsed_secs".into(), json!(summary.elapsed.as_secs_f64()));
+    fields
+}
+
+/// Builds the `--summary-json` report: just the counts and bytes per bucket,
+/// and how long the run took.
+pub fn summary_json(summary: &Summary) -> Value {
+    let (counts, bytes) = count_maps(summary);
+    json!({
+        "counts": counts,
+        "total": summary.total_files(),
+        "bytes": bytes,
+        "total_bytes": summary.total_bytes(),
+        "elapsed_secs": summary.elapsed.as_secs_f64(),
+    })
+}
+
+/// Files per bucket and bytes per category, in summary order.
+fn count_maps(summary: &Summary) -> (Map<String, Value>, Map<String, Value>) {
+    let counts = summary.rows().into_iter().map(|(k, v)| (k.to_string(), json!(v))).collect();
+    let bytes = summary
+        .rows()
+        .into_iter()
+        .filter(|(k, _)| summary.bytes.contains_key(*k))
+        .map(|(k, _)| (k.to_string(), json!(summary.bytes(k))))
+        .collect();
+    (counts, bytes)
+}
+
+/// JSON report for `--report-only-others`: every file that would go to
+/// "Others", by extension and then by name.
+pub fn others_json(root: &Path, stats: &FolderStats) -> Value {
+    let files: Vec<Value> = stats
+        .others_rows()
+        .into_iter()
+        .map(|file| json!({ "path": file.path.display().to_string(), "extension": file.ext, "bytes": file.size }))
+        .collect();
+    json!({
+        "folder": root.display().to_string(),
+        "others": files,
+        "skipped": stats.skipped,
+        "errors": stats.failures.len(),
+        "failures": stats.failures.iter().map(action_to_json).collect::<Vec<_>>(),
+    })
+}
+
+/// JSON report for `--extension-report`: every extension found, most files first.
+pub fn extensions_json(root: &Path, stats: &FolderStats) -> Value {
+    let extensions: Vec<Value> = stats
+        .extension_rows()
+        .into_iter()
+        .map(|(ext, count)| {
+            json!({ "extension": ext, "files": count.files, "bytes": count.bytes, "category": count.category })
+        })
+        .collect();
+    json!({
+        "folder": root.display().to_string(),
+        "extensions": extensions,
+        "skipped": stats.skipped,
+        "errors": stats.failures.len(),
+        "failures": stats.failures.iter().map(action_to_json).collect::<Vec<_>>(),
+    })
+}
+
+/// Builds the `--stats-only --json` report: files and bytes per category and the largest files.
+pub fn stats_json(root: &Path, stats: &FolderStats) -> Value {
+    let categories: Map<String, Value> = stats
+        .rows()
+        .into_iter()
+        .map(|(k, files, bytes)| (k.to_string(), json!({ "files": files, "bytes": bytes })))
+        .collect();
+    let largest: Vec<Value> = stats
+        .largest
+        .iter()
+        .map(|f| json!({ "path": f.path.display().to_string(), "size": f.size, "category": f.category }))
+        .collect();
+    json!({
+        "folder": root.display().to_string(),
+        "categories": categories,
+        "total": stats.total_files(),
+        "total_bytes": stats.total_bytes(),
+        "skipped": stats.skipped,
+        "errors": stats.failures.len(),
+        "largest": largest,
+        "failures": stats.failures.iter().map(action_to_json).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::SkipReason;
+
+    #[test]
+    fn streamed_json_report_matches_the_built_one() {
+        let mut summary = Summary::default();
+        summary.record(FileAction::skipped("/in/.hidden".into(), SkipReason::Hidden));
+        summary.record(FileAction::skipped("/in/notes.tmp".into(), SkipReason::Unmatched));
+        let options = Options::default();
+        let mut written = Vec::new();
+        let mut report = JsonReport::start(&mut written).unwrap();
+        for action in &summary.actions {
+            report.add(action).unwrap();
+        }
+        report.finish(Path::new("/in"), &options, &summary).unwrap();
+
+        assert!(written.starts_with(b"{\"actions\":[{"));
+        let written: Value = serde_json::from_slice(&written).unwrap();
+        assert_eq!(written, json_report(Path::new("/in"), &options, &summary));
+    }
+
+    #[test]
+    fn csv_rows_follow_the_header() {
+        let placed = FileAction {
+            destination: Some("/in/Images/a, b.jpg".into()),
+            category: Some("Images".to_string()),
+            size: Some(3),
+            status: ActionStatus::WouldCopy,
+            ..FileAction::skipped("/in/a, b.jpg".into(), SkipReason::Hidden)
+        };
+        let mut written = Vec::new();
+        let mut report = CsvReport::start(&mut written).unwrap();
+        report.add(&placed).unwrap();
+        report.add(&FileAction::skipped("/in/photos".into(), SkipReason::NotRecursive)).unwrap();
+        report.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "source,destination,category,size,status\n\"/in/a, b.jpg\",\"/in/Images/a, b.jpg\",Images,3,would_copy\n"
+        );
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("/in/cat.jpg"), "/in/cat.jpg");
+        assert_eq!(csv_field("/in/a, b.jpg"), "\"/in/a, b.jpg\"");
+        assert_eq!(csv_field("say \"hi\".txt"), "\"say \"\"hi\"\".txt\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
@@ -0,0 +1,177 @@
+//! `--rules FILE`: classification rules for what extensions alone can't say,
+//! e.g. screenshots or big videos.
+//!
+//! ```toml
+//! [[rule]]
+//! category = "Screenshots"
+//! name = "Screenshot*"
+//! extensions = ["png", "jpg"]
+//!
+//! [[rule]]
+//! category = "Old Videos"
+//! extensions = ["mp4", "mov"]
+//! min_size = "500MB"
+//! until = "2020-12-31"
+//! ```
+//!
+//! Rules are tried from top to bottom and the first one that matches decides
+//! the category, so put the narrow rules above the broad ones. A rule matches
+//! when all of its conditions do; a rule without any condition matches every
+//! file. Files no rule matches are classified by extension as usual.
+
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::dates::{Bound, parse_date_bound};
+use crate::size::parse_size;
+
+/// One rule: the category it gives and the conditions a file has to meet.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub category: String,
+    /// Extensions, lowercase without the dot; empty = any.
+    pub extensions: Vec<String>,
+    /// Glob the file name has to match.
+    pub name: Option<glob::Pattern>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Modified at or after this time.
+    pub since: Option<SystemTime>,
+    /// Modified at or before this time.
+    pub until: Option<SystemTime>,
+}
+
+/// A rule as written in the file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    category: String,
+    #[serde(default)]
+    extensions: Vec<String>,
+    name: Option<String>,
+    min_size: Option<String>,
+    max_size: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RawRule>,
+}
+
+impl Rule {
+    /// Whether `path` (named `name`, with extension `ext`) meets every condition.
+    fn matches(&self, path: &Path, name: &str, ext: &str) -> bool {
+        if !self.extensions.is_empty() && !self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)) {
+            return false;
+        }
+        if self.name.as_ref().is_some_and(|pattern| !pattern.matches(name)) {
+            return false;
+        }
+        let needs_meta =
+            self.min_size.is_some() || self.max_size.is_some() || self.since.is_some() || self.until.is_some();
+        if !needs_meta {
+            return true;
+        }
+        // A file that can't be looked at doesn't meet a condition on it
+        let Ok(meta) = fs::metadata(path) else { return false };
+        let len = meta.len();
+        if self.min_size.is_some_and(|min| len < min) || self.max_size.is_some_and(|max| len > max) {
+            return false;
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let Ok(modified) = meta.modified() else { return false };
+            if self.since.is_some_and(|since| modified < since) || self.until.is_some_and(|until| modified > until) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Category the first matching rule gives `path`, if any.
+pub fn category_for<'a>(rules: &'a [Rule], path: &Path, ext: &str) -> Option<&'a str> {
+    first_match(rules, path, ext).map(|i| rules[i].category.as_str())
+}
+
+/// Index of the first rule `path` matches.
+pub fn first_match(rules: &[Rule], path: &Path, ext: &str) -> Option<usize> {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    rules.iter().position(|rule| rule.matches(path, name, ext))
+}
+
+/// Reads a rules file (TOML, see the module docs).
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read rules '{}': {}", path.display(), e))?;
+    parse_rules(&text).map_err(|e| format!("invalid rules in '{}': {}", path.display(), e))
+}
+
+fn parse_rules(text: &str) -> Result<Vec<Rule>, String> {
+    let file: RulesFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    let now = SystemTime::now();
+    file.rule
+        .into_iter()
+        .enumerate()
+        .map(|(i, raw)| {
+            let context = |e: String| format!("rule {} ('{}'): {}", i + 1, raw.category, e);
+            let category = raw.category.trim();
+            if category.is_empty() || category == "." || category == ".." || category.contains(['/', '\\']) {
+                return Err(context("the category must be usable as a folder name".to_string()));
+            }
+            let size = |value: &Option<String>| value.as_deref().map(parse_size).transpose().map_err(context);
+            let date = |value: &Option<String>, bound| {
+                value.as_deref().map(|v| parse_date_bound(v, bound, now)).transpose().map_err(context)
+            };
+            Ok(Rule {
+                category: category.to_string(),
+                extensions: raw.extensions.iter().map(|e| e.trim_start_matches('.').to_lowercase()).collect(),
+                name: raw
+                    .name
+                    .as_deref()
+                    .map(glob::Pattern::new)
+                    .transpose()
+                    .map_err(|e| context(format!("invalid name pattern: {}", e)))?,
+                min_size: size(&raw.min_size)?,
+                max_size: size(&raw.max_size)?,
+                since: date(&raw.since, Bound::Start)?,
+                until: date(&raw.until, Bound::End)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = parse_rules(
+            r#"
+            [[rule]]
+            category = "Screenshots"
+            name = "Screenshot*"
+            extensions = [".PNG"]
+
+            [[rule]]
+            category = "Pictures"
+            extensions = ["png"]
+            "#,
+        )
+        .unwrap();
+        let shot = Path::new("Screenshot 1.png");
+        let photo = Path::new("cat.png");
+        assert_eq!(category_for(&rules, shot, "png"), Some("Screenshots"));
+        assert_eq!(category_for(&rules, photo, "png"), Some("Pictures"));
+        assert_eq!(category_for(&rules, photo, "jpg"), None);
+
+        assert!(parse_rules("[[rule]]\ncategory = \"Big\"\nmin_size = \"lots\"\n").is_err());
+        assert!(parse_rules("[[rule]]\ncategory = \"a/b\"\n").is_err());
+        assert!(parse_rules("[[rule]]\ncategory = \"X\"\nsize = 3\n").is_err());
+    }
+}
@@ -0,0 +1,1384 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::archive::{self, is_zip};
+use crate::dates::{DateField, UNKNOWN_DATE_FOLDER, date_folder};
+use crate::exif::{EXIF_EXTENSIONS, date_taken};
+use crate::hash::{file_sha256, files_identical};
+use crate::metadata::{copy_owner, copy_xattrs};
+use crate::oplog::OperationLog;
+use crate::owner::{Owners, UNKNOWN_OWNER_FOLDER};
+use crate::prefix::{common_prefixes, name_prefix};
+use crate::rename::apply_rules;
+use crate::rules;
+use crate::sniff::{sniff_extension, sniff_type};
+use crate::state::{STATE_FILE, last_run, save_last_run};
+use crate::stats::FolderStats;
+use crate::transfer::{
+    copy_new, copy_times, create_dir_all, explain_path_error, link_new, move_file, move_new,
+    unique_destination_with_format,
+    windows_safe_name, with_retries,
+};
+use crate::trash::{FALLBACK_TRASH_DIR, Trashed, trash_file};
+use crate::{
+    ActionStatus, Change, Conflict, ConflictPolicy, Decision, EmptyDirs, ExtensionIndex, Failure, FailureKind,
+    FileAction, HIDDEN_CATEGORY, HiddenFiles, Options, OthersPolicy, PlannedAction, SMALL_FILES_CATEGORY, SkipReason,
+    Summary, category_for_mime, file_extension, is_category_dir, is_hidden, subcategory_for_extension,
+};
+
+/// Confirmation callback shared by all workers of a run.
+pub(crate) type ConfirmFn<'a> = &'a mut (dyn FnMut(&PlannedAction) -> Decision + Send);
+
+/// How [`Run::classify`] sorted a file.
+struct Classification {
+    /// Extension of the file's name ("" if none).
+    ext: String,
+    category: String,
+    /// Type recognized from the contents, if that decided the category.
+    content_type: Option<String>,
+    /// Extension subcategories are looked up by: the one the contents
+    /// correspond to if they decided, otherwise `ext`.
+    lookup_ext: String,
+}
+
+/// Outcome of placing a file: status, destination and how a name collision was resolved.
+type Placement = (ActionStatus, Option<PathBuf>, Option<Conflict>);
+
+/// Progress callback: number of files handled so far and the file just handled.
+pub(crate) type ProgressFn<'a> = &'a mut (dyn FnMut(usize, &Path) + Send);
+
+/// Callback given every action as it is recorded.
+pub(crate) type ActionFn<'a> = &'a mut (dyn FnMut(&FileAction) + Send);
+
+/// Turns `--output` into an absolute path, creating it unless this is a dry run.
+pub(crate) fn resolve_output_root(output: &Path, dry_run: bool) -> io::Result<PathBuf> {
+    if !dry_run {
+        fs::create_dir_all(output)?;
+    }
+    if output.exists() {
+        let out = fs::canonicalize(output)?;
+        if !out.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("output '{}' is not a directory", out.display()),
+            ));
+        }
+        Ok(out)
+    } else {
+        std::path::absolute(output)
+    }
+}
+
+/// State for a single organize run. Everything mutable sits behind a lock so
+/// files can be processed by several worker threads (`--jobs`).
+pub(crate) struct Run<'a> {
+    opts: &'a Options,
+    /// Where category folders are created.
+    out_root: &'a Path,
+    summary: Mutex<Summary>,
+    confirm: Mutex<ConfirmFn<'a>>,
+    /// Files handled so far, paired with the progress callback.
+    progress: Mutex<(usize, Option<ProgressFn<'a>>)>,
+    on_action: Mutex<Option<ActionFn<'a>>>,
+    /// `--dedup`: (category, content hash) -> source of the first file placed with it.
+    seen_hashes: Mutex<HashMap<(String, String), PathBuf>>,
+    log: Mutex<Option<OperationLog>>,
+    /// Destinations handed out this run, so two workers never pick the same name.
+    reserved: Mutex<HashSet<PathBuf>>,
+    /// Folders this run created, removed again at the end if nothing landed in them.
+    created_dirs: Mutex<HashSet<PathBuf>>,
+    stopped: AtomicBool,
+    /// Files copied or moved so far, counted against `--limit` before they are.
+    acted: AtomicUsize,
+    /// Set once `--preserve-times` turned out to be unsupported, so that's only reported once.
+    times_unsupported: AtomicBool,
+    /// Set once `--preserve all` couldn't keep owners, so that's only reported once.
+    owner_unsupported: AtomicBool,
+    /// Set once `--preserve all` couldn't keep extended attributes, so that's only reported once.
+    xattrs_unsupported: AtomicBool,
+    /// Set once `--date-field created` had to fall back to modified times, so that's only reported once.
+    created_unsupported: AtomicBool,
+    /// Set once `--trash-on-overwrite` fell back to the output folder's trash, so that's only reported once.
+    trash_fallback_reported: AtomicBool,
+    /// `--by-prefix`: name prefixes shared by enough of the run's files to get a folder.
+    prefixes: HashSet<String>,
+    /// `opts.categories` by extension.
+    extensions: ExtensionIndex<'a>,
+    /// Folder being walked, that `--keep-structure` paths are relative to.
+    source_root: Option<PathBuf>,
+    /// `--by-owner`: user names by uid.
+    owners: Option<Owners>,
+    /// `--since-last-run`: files modified before this are left alone.
+    last_run: Option<SystemTime>,
+}
+
+impl<'a> Run<'a> {
+    pub fn new(opts: &'a Options, out_root: &'a Path, confirm: ConfirmFn<'a>, log: Option<OperationLog>) -> Self {
+        Run {
+            opts,
+            out_root,
+            summary: Mutex::new(Summary::new(opts)),
+            confirm: Mutex::new(confirm),
+            progress: Mutex::new((0, None)),
+            on_action: Mutex::new(None),
+            seen_hashes: Mutex::new(HashMap::new()),
+            log: Mutex::new(log),
+            reserved: Mutex::new(HashSet::new()),
+            created_dirs: Mutex::new(HashSet::new()),
+            stopped: AtomicBool::new(false),
+            acted: AtomicUsize::new(0),
+            times_unsupported: AtomicBool::new(false),
+            owner_unsupported: AtomicBool::new(false),
+            xattrs_unsupported: AtomicBool::new(false),
+            created_unsupported: AtomicBool::new(false),
+            trash_fallback_reported: AtomicBool::new(false),
+            prefixes: HashSet::new(),
+            extensions: ExtensionIndex::new(&opts.categories, opts.case_sensitive, &opts.priority),
+            source_root: None,
+            owners: opts.by_owner.then(Owners::load),
+            last_run: None,
+        }
+    }
+
+    pub fn with_progress(mut self, progress: ProgressFn<'a>) -> Self {
+        self.progress = Mutex::new((0, Some(progress)));
+        self
+    }
+
+    pub fn with_action_callback(mut self, on_action: ActionFn<'a>) -> Self {
+        self.on_action = Mutex::new(Some(on_action));
+        self
+    }
+
+    /// Counts the files a run over `root` would hand to [`Run::process_file`],
+    /// without looking at them any further.
+    pub fn count_files(&self, root: &Path) -> io::Result<usize> {
+        let mut count = 0;
+        self.process_directory(root, 0, &mut vec![root.to_path_buf()], &mut |_| count += 1)?;
+        Ok(count)
+    }
+
+    /// Organizes `root`, spreading the files over `opts.jobs` worker threads.
+    pub fn execute(mut self, root: &Path) -> io::Result<Summary> {
+        let started = Instant::now();
+        let started_at = SystemTime::now();
+        self.source_root = Some(root.to_path_buf());
+        if self.opts.since_last_run {
+            self.last_run = last_run(self.out_root, root);
+        }
+        if self.opts.by_prefix {
+            // Prefixes are counted over the whole run, so every file has to be found first
+            let mut files = Vec::new();
+            self.process_directory(root, 0, &mut vec![root.to_path_buf()], &mut |path| files.push(path))?;
+            self.prefixes = self.find_prefixes(&files);
+            self.dispatch(|on_file| {
+                files.into_iter().for_each(on_file);
+                Ok(())
+            })?;
+        } else {
+            self.dispatch(|on_file| self.process_directory(root, 0, &mut vec![root.to_path_buf()], on_file))?;
+        }
+
+        remove_unused_dirs(self.created_dirs.get_mut().unwrap());
+        let mut summary = self.summary.into_inner().unwrap();
+        summary.stopped = self.stopped.into_inner();
+        summary.limit_used = self.opts.limit.map_or(0, |limit| self.acted.load(Ordering::SeqCst).min(limit));
+        // A dry run moved nothing, so there's nothing to put back
+        if self.opts.transactional && !self.opts.dry_run && !summary.failures_by_kind().is_empty() {
+            summary = roll_back(summary, self.out_root);
+        }
+        if self.opts.move_files && !self.opts.dry_run && !summary.stopped {
+            match self.opts.remove_empty_dirs {
+                EmptyDirs::Keep => {}
+                EmptyDirs::Emptied => summary.removed_dirs = remove_emptied_dirs(root, &summary.actions),
+                EmptyDirs::All => remove_empty_dirs(root, self.out_root, self.opts, &mut summary.removed_dirs),
+            }
+        }
+        if self.opts.since_last_run
+            && !self.opts.dry_run
+            && !summary.stopped
+            && summary.count("Errors") == 0
+            && let Err(e) = save_last_run(self.out_root, root, started_at)
+        {
+            let failure = Failure::io("could not save the time of this run", &e);
+            let action = FileAction::failed(self.out_root.join(STATE_FILE), failure);
+            if let Some(on_action) = self.on_action.get_mut().unwrap().as_mut() {
+                on_action(&action);
+            }
+            summary.record(action);
+        }
+        summary.elapsed = started.elapsed();
+        Ok(summary)
+    }
+
+    /// Organizes exactly the given files instead of walking a folder. Entries
+    /// that aren't existing regular files are counted as errors.
+    pub fn execute_files(mut self, files: Vec<PathBuf>) -> Summary {
+        let started = Instant::now();
+        let checked = self.opts.by_prefix;
+        let files = if checked {
+            // Prefixes are counted over all the files, so they're checked up front
+            let listed: Vec<PathBuf> = files.into_iter().filter_map(|path| self.check_listed(path)).collect();
+            self.prefixes = self.find_prefixes(&listed);
+            listed
+        } else {
+            files
+        };
+        let _ = self.dispatch(|on_file| {
+            for path in files {
+                if self.stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                let path = if checked { Some(path) } else { self.check_listed(path) };
+                if let Some(path) = path {
+                    on_file(path);
+                }
+            }
+            Ok(())
+        });
+
+        remove_unused_dirs(self.created_dirs.get_mut().unwrap());
+        let mut summary = self.summary.into_inner().unwrap();
+        summary.stopped = self.stopped.into_inner();
+        summary.limit_used = self.opts.limit.map_or(0, |limit| self.acted.load(Ordering::SeqCst).min(limit));
+        if self.opts.transactional && !self.opts.dry_run && !summary.failures_by_kind().is_empty() {
+            summary = roll_back(summary, self.out_root);
+        }
+        summary.elapsed = started.elapsed();
+        summary
+    }
+
+    /// The prefixes `--by-prefix` makes folders for: those shared by at least
+    /// `--prefix-min` of the `files` that won't be skipped.
+    fn find_prefixes(&self, files: &[PathBuf]) -> HashSet<String> {
+        let names = files
+            .iter()
+            .filter(|path| matches!(self.skip_reason(path, &fs::metadata(path)), Ok(None)))
+            .filter_map(|path| path.file_name()?.to_str());
+        common_prefixes(names, self.opts.prefix_min)
+    }
+
+    /// Runs `produce`, which passes files to its callback, and handles those
+    /// files on `opts.jobs` worker threads (or right away with one job).
+    fn dispatch(&self, produce: impl FnOnce(&mut dyn FnMut(PathBuf)) -> io::Result<()>) -> io::Result<()> {
+        let jobs = self.opts.jobs.max(1);
+        if jobs == 1 {
+            return produce(&mut |path| self.handle_file(path));
+        }
+        let (tx, rx) = mpsc::sync_channel::<PathBuf>(jobs * 4);
+        let rx = Mutex::new(rx);
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| {
+                    loop {
+                        let next = rx.lock().unwrap().recv();
+                        match next {
+                            Ok(path) => self.handle_file(path),
+                            Err(_) => break,
+                        }
+                    }
+                });
+            }
+            let result = produce(&mut |path| {
+                // Only fails if every worker is gone, which means they panicked
+                let _ = tx.send(path);
+            });
+            drop(tx);
+            result
+        })
+    }
+
+    /// Checks a path given by the caller before it's handled like a file
+    /// found by walking a folder. Returns it (made absolute) if it should be,
+    /// recording why not otherwise.
+    fn check_listed(&self, path: PathBuf) -> Option<PathBuf> {
+        let path = std::path::absolute(&path).unwrap_or(path);
+        let meta = match fs::symlink_metadata(&path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                self.record(FileAction::failed(path, Failure::io("could not read file", &e)));
+                return None;
+            }
+        };
+        if meta.file_type().is_symlink() && !self.opts.follow_symlinks {
+            self.record(FileAction::skipped(path, SkipReason::Symlink));
+            return None;
+        }
+        let is_file = match fs::metadata(&path) {
+            Ok(meta) => meta.is_file(),
+            Err(e) => {
+                self.record(FileAction::failed(path, Failure::io("broken symlink", &e)));
+                return None;
+            }
+        };
+        if !is_file {
+            let failure = Failure {
+                kind: FailureKind::Other,
+                message: "not a regular file".to_string(),
+            };
+            self.record(FileAction::failed(path, failure));
+            return None;
+        }
+        // Files that were already sorted into the output folders are left there
+        let rel = path.strip_prefix(self.out_root).ok();
+        let first = rel
+            .filter(|rel| rel.components().count() > 1)
+            .and_then(|rel| rel.components().next());
+        let in_destination = || {
+            path.ancestors()
+                .skip(1)
+                .any(|dir| is_destination(dir, self.out_root, self.opts))
+        };
+        if first.is_some_and(|first| is_output_dir(&self.out_root.join(first), self.out_root, self.opts))
+            || in_destination()
+        {
+            self.record(FileAction::skipped(path, SkipReason::AlreadySorted));
+            return None;
+        }
+        Some(path)
+    }
+
+    fn record(&self, action: FileAction) {
+        let failed = matches!(action.status, ActionStatus::Failed(_));
+        if (self.opts.fail_fast && (failed || action.warning.is_some())) || (self.opts.transactional && failed) {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        // Called under the summary's lock, so callbacks come in the order of `Summary::actions`
+        let mut summary = self.summary.lock().unwrap();
+        if let Some(on_action) = self.on_action.lock().unwrap().as_mut() {
+            on_action(&action);
+        }
+        if self.keeps(&action) {
+            summary.record(action);
+        } else {
+            summary.tally(&action);
+        }
+    }
+
+    /// Whether `action` stays in [`Summary::actions`] (see [`Options::keep_actions`]).
+    fn keeps(&self, action: &FileAction) -> bool {
+        let undone = self.opts.transactional || self.opts.remove_empty_dirs == EmptyDirs::Emptied;
+        self.opts.keep_actions
+            || matches!(action.status, ActionStatus::Failed(_))
+            || action.warning.is_some()
+            || (undone && action.status == ActionStatus::Moved)
+    }
+
+    fn handle_file(&self, path: PathBuf) {
+        if self.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.opts.extract_archives && is_zip(&path) {
+            self.extract_archive(&path);
+        } else {
+            let action = self.process_file(path.clone(), None);
+            self.record(action);
+        }
+
+        let mut progress = self.progress.lock().unwrap();
+        let (done, callback) = &mut *progress;
+        *done += 1;
+        if let Some(callback) = callback {
+            callback(*done, &path);
+        }
+    }
+
+    /// Walks `dir`, passing every regular file to `on_file` and descending
+    /// into subfolders when recursion is enabled. The output root and its
+    /// category folders are never descended into so already-sorted files
+    /// aren't picked up again. `ancestors` holds the resolved paths of `dir`
+    /// and the folders above it, so a followed symlink can't lead back into one.
+    fn process_directory(
+        &self,
+        dir: &Path,
+        depth: usize,
+        ancestors: &mut Vec<PathBuf>,
+        on_file: &mut dyn FnMut(PathBuf),
+    ) -> io::Result<()> {
+        let opts = self.opts;
+        for entry in fs::read_dir(dir)? {
+            if self.stopped.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let dir_entry = match entry {
+                Ok(dir_entry) => dir_entry,
+                Err(e) => {
+                    self.record(FileAction::failed(
+                        dir.to_path_buf(),
+                        Failure::io("failed to read an entry", &e),
+                    ));
+                    continue;
+                }
+            };
+
+            let path = dir_entry.path();
+            let mut file_type = match dir_entry.file_type() {
+                Ok(ft) => ft,
+                Err(e) => {
+                    self.record(FileAction::failed(path, Failure::io("could not read file type", &e)));
+                    continue;
+                }
+            };
+
+            if file_type.is_symlink() {
+                if !opts.follow_symlinks {
+                    // skip symlinks for safety (this also avoids directory cycles)
+                    self.record(FileAction::skipped(path, SkipReason::Symlink));
+                    continue;
+                }
+                // Links that point at each other fail here too
+                match fs::metadata(&path) {
+                    Ok(meta) => file_type = meta.file_type(),
+                    Err(e) => {
+                        let context = if e.kind() == io::ErrorKind::NotFound {
+                            "broken symlink"
+                        } else {
+                            "could not follow symlink"
+                        };
+                        self.record(FileAction::failed(path, Failure::io(context, &e)));
+                        continue;
+                    }
+                }
+            }
+
+            if file_type.is_dir() {
+                if is_output_dir(&path, self.out_root, opts) {
+                    self.record(FileAction::skipped(path, SkipReason::OutputFolder));
+                    continue;
+                }
+                if !opts.recursive {
+                    self.record(FileAction::skipped(path, SkipReason::NotRecursive));
+                    continue;
+                }
+                if opts.max_depth.is_some_and(|max| depth >= max) {
+                    self.record(FileAction::skipped(path, SkipReason::TooDeep));
+                    continue;
+                }
+                if opts.hidden == HiddenFiles::Skip && is_hidden(&path) {
+                    self.record(FileAction::skipped(path, SkipReason::Hidden));
+                    continue;
+                }
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if let Some(pattern) = opts.ignore.iter().find(|p| p.matches(name)) {
+                    let reason = SkipReason::Ignored(pattern.as_str().to_string());
+                    self.record(FileAction::skipped(path, reason));
+                    continue;
+                }
+                let resolved = if opts.follow_symlinks {
+                    match fs::canonicalize(&path) {
+                        Ok(resolved) => resolved,
+                        Err(e) => {
+                            self.record(FileAction::failed(path, Failure::io("could not resolve directory", &e)));
+                            continue;
+                        }
+                    }
+                } else {
+                    path.clone()
+                };
+                if let Some(ancestor) = ancestors.iter().find(|a| **a == resolved) {
+                    let failure = Failure {
+                        kind: FailureKind::Other,
+                        message: format!("symlink loop: leads back to '{}'", ancestor.display()),
+                    };
+                    self.record(FileAction::failed(path, failure));
+                    continue;
+                }
+                // A link may point into the output folders even though its own path doesn't
+                if is_output_dir(&resolved, self.out_root, opts) {
+                    self.record(FileAction::skipped(path, SkipReason::OutputFolder));
+                    continue;
+                }
+                ancestors.push(resolved);
+                let result = self.process_directory(&path, depth + 1, ancestors, on_file);
+                ancestors.pop();
+                if let Err(e) = result {
+                    self.record(FileAction::failed(path, Failure::io("failed to read directory", &e)));
+                }
+                continue;
+            }
+
+            // The run's own bookkeeping, not one of the user's files
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if dir == self.out_root && name.starts_with(STATE_FILE) {
+                continue;
+            }
+            on_file(path);
+        }
+
+        Ok(())
+    }
+
+    /// Classifies a single regular file and copies/moves it into its category
+    /// folder (or just plans it, in a dry run). `origin` is where it came from
+    /// when that isn't `path` itself: a file unpacked from an archive is shown
+    /// as `photos.zip/cat.jpg` rather than by its staging copy.
+    fn process_file(&self, path: PathBuf, origin: Option<&Path>) -> FileAction {
+        let opts = self.opts;
+        let shown = origin.unwrap_or(&path);
+        let meta = fs::metadata(&path);
+        match self.skip_reason(&path, &meta) {
+            Ok(None) => {}
+            Ok(Some(reason)) => return FileAction::skipped(path, reason),
+            Err(failure) => return FileAction::failed(path, failure),
+        }
+        let Classification {
+            ext,
+            category,
+            content_type,
+            lookup_ext,
+        } = self.classify(&path);
+        if category == "Others" {
+            match opts.others {
+                OthersPolicy::Folder => {}
+                OthersPolicy::Leave => return FileAction::skipped(path, SkipReason::Unmatched),
+                OthersPolicy::Error => {
+                    let failure = Failure {
+                        kind: FailureKind::Unrecognized,
+                        message: match ext.as_str() {
+                            "" => "no extension, so no category matched".to_string(),
+                            ext => format!("no category for extension '{}'", ext),
+                        },
+                    };
+                    return FileAction::failed(path, failure);
+                }
+            }
+        }
+
+        let mut warning = None;
+        let mut add_warning = |message: String| {
+            warning = Some(match warning.take() {
+                Some(earlier) => format!("{}; {}", earlier, message),
+                None => message,
+            });
+        };
+        let mut subcategory = None;
+        let base = match &self.owners {
+            Some(owners) => self.out_root.join(owners.folder(&path).unwrap_or_else(|e| {
+                add_warning(format!("could not read the owner: {}", e));
+                UNKNOWN_OWNER_FOLDER.to_string()
+            })),
+            None => self.out_root.to_path_buf(),
+        };
+        let mut dest_dir = if opts.by_date && opts.date_only {
+            base
+        } else if let Some(dest) = opts.destinations.get(&category) {
+            base.join(dest)
+        } else {
+            base.join(opts.folder_name(&category))
+        };
+        if opts.subcategorize && !(opts.by_date && opts.date_only) {
+            subcategory = subcategory_for_extension(&category, &lookup_ext, &opts.subcategories, opts.case_sensitive)
+                .map(|sub| {
+                    dest_dir.push(sub);
+                    sub.to_string()
+                });
+        }
+        if opts.keep_structure
+            && let Some(rel) = self
+                .source_root
+                .as_deref()
+                .and_then(|root| path.parent()?.strip_prefix(root).ok())
+        {
+            dest_dir.push(rel);
+        }
+        if let Some(pattern) = &opts.tag_pattern
+            && let Some(tag) = path.file_name().and_then(|name| name.to_str()).and_then(|name| pattern.tag_of(name))
+        {
+            dest_dir.push(tag);
+        }
+        if opts.by_prefix
+            && let Some(prefix) = path.file_name().and_then(|name| name.to_str()).and_then(name_prefix)
+            && self.prefixes.contains(prefix)
+        {
+            dest_dir.push(prefix);
+        }
+        if opts.by_date {
+            let has_exif = opts.by_exif_date && EXIF_EXTENSIONS.iter().any(|e| lookup_ext.eq_ignore_ascii_case(e));
+            let taken = if has_exif { date_taken(&path) } else { None };
+            let time = match taken {
+                Some(taken) => Ok(taken),
+                None => fs::metadata(&path).and_then(|meta| match opts.date_field.of(&meta) {
+                    Err(e) if e.kind() == io::ErrorKind::Unsupported && opts.date_field == DateField::Created => {
+                        if !self.created_unsupported.swap(true, Ordering::Relaxed) {
+                            add_warning(
+                                "creation times aren't recorded here; using modified times instead".to_string(),
+                            );
+                        }
+                        meta.modified()
+                    }
+                    time => time,
+                }),
+            };
+            match time {
+                Ok(time) => dest_dir.push(date_folder(time, &opts.date_format)),
+                Err(e) => {
+                    add_warning(format!("could not read {} time: {}", opts.date_field, e));
+                    dest_dir.push(UNKNOWN_DATE_FOLDER);
+                }
+            }
+        }
+
+        // Running twice (or on a category folder) shouldn't copy files next to themselves
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let rel = dest_dir
+            .strip_prefix(self.out_root)
+            .ok()
+            .filter(|rel| !rel.as_os_str().is_empty());
+        if parent == dest_dir || rel.is_some_and(|rel| parent.ends_with(rel)) {
+            return FileAction {
+                category: Some(category),
+                ..FileAction::skipped(path, SkipReason::AlreadyOrganized)
+            };
+        }
+
+        let (status, destination, conflict) = self.place(&path, shown, &category, &dest_dir, &mut add_warning);
+        let change = if opts.diff && opts.dry_run {
+            self.change(&path, &dest_dir, &status)
+        } else {
+            None
+        };
+
+        // A link has its file's timestamps and owner already
+        let (keep_times, keep_metadata) = match opts.link {
+            Some(_) => (false, false),
+            None => (opts.preserve_times, opts.preserve_metadata),
+        };
+        if let (Some(dest), ActionStatus::Copied, true) = (destination.as_deref(), &status, keep_times)
+            && let Err(e) = copy_times(&path, dest)
+        {
+            if e.kind() != io::ErrorKind::Unsupported {
+                add_warning(format!("could not preserve timestamps: {}", e));
+            } else if !self.times_unsupported.swap(true, Ordering::Relaxed) {
+                add_warning("timestamps can't be preserved on this platform; continuing without".to_string());
+            }
+        }
+        if let (Some(dest), ActionStatus::Copied, true) = (destination.as_deref(), &status, keep_metadata) {
+            self.copy_metadata(&path, dest, &mut add_warning);
+        }
+        // Unpacked files aren't logged: undoing can't put them back into the archive
+        if let (Some(dest), ActionStatus::Copied | ActionStatus::Moved) = (destination.as_deref(), &status)
+            && origin.is_none()
+            && let Some(log) = self.log.lock().unwrap().as_mut()
+            && let Err(e) = log.append(opts.verb(), &path, dest)
+        {
+            add_warning(format!("could not write to log: {}", e));
+        }
+        if let (Some(dest), Some(hook), false) = (destination.as_deref(), &opts.exec, opts.dry_run)
+            && status.is_placed()
+            && let Err(e) = hook.run(shown, dest, &category)
+        {
+            add_warning(e);
+        }
+        FileAction {
+            source: path,
+            destination,
+            category: Some(category),
+            subcategory,
+            content_type,
+            status,
+            size: meta.ok().map(|m| m.len()),
+            conflict,
+            warning,
+            change,
+        }
+    }
+
+    /// `--extract-archives`: unpacks the zip at `path` into a staging folder
+    /// and sorts each file in it like one found next to the zip. After a move
+    /// the zip is removed once everything in it has been placed.
+    fn extract_archive(&self, path: &Path) {
+        let meta = fs::metadata(path);
+        match self.skip_reason(path, &meta) {
+            Ok(None) => {}
+            Ok(Some(reason)) => return self.record(FileAction::skipped(path.to_path_buf(), reason)),
+            Err(failure) => return self.record(FileAction::failed(path.to_path_buf(), failure)),
+        }
+        static STAGED: AtomicUsize = AtomicUsize::new(0);
+        let staging = std::env::temp_dir().join(format!(
+            "file-organizer-{}-{}",
+            std::process::id(),
+            STAGED.fetch_add(1, Ordering::Relaxed)
+        ));
+        let contents = archive::extract(path, &staging, self.opts.extract_nested);
+        let contents = match contents {
+            Ok(contents) => contents,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging);
+                let failure = Failure::io("could not extract the archive", &e);
+                return self.record(FileAction::failed(path.to_path_buf(), failure));
+            }
+        };
+
+        let mut all_placed = contents.refused.is_empty();
+        for file in contents.files {
+            if self.stopped.load(Ordering::Relaxed) {
+                all_placed = false;
+                break;
+            }
+            let source = path.join(&file.inner);
+            let action = self.process_file(file.staged, Some(&source));
+            all_placed &= action.status.is_placed()
+                || matches!(action.status, ActionStatus::Duplicate(_) | ActionStatus::Skipped(SkipReason::Identical));
+            self.record(FileAction { source, ..action });
+        }
+        let _ = fs::remove_dir_all(&staging);
+        for (name, why) in contents.refused {
+            let failure = Failure {
+                kind: FailureKind::Other,
+                message: format!("not extracted: {}", why),
+            };
+            let source = PathBuf::from(format!("{}/{}", path.display(), name));
+            self.record(FileAction::failed(source, failure));
+        }
+        if self.opts.move_files
+            && !self.opts.dry_run
+            && all_placed
+            && let Err(e) = fs::remove_file(path)
+        {
+            let failure = Failure::io("extracted, but could not remove the archive", &e);
+            self.record(FileAction::failed(path.to_path_buf(), failure));
+        }
+    }
+
+    /// `--preserve all`: gives the copy `dest` the owner, group and extended
+    /// attributes of `src`. What the platform or missing permissions rule out
+    /// is reported once per run, other failures for each file.
+    fn copy_metadata(&self, src: &Path, dest: &Path, add_warning: &mut dyn FnMut(String)) {
+        let results = [
+            ("owner", copy_owner(src, dest), &self.owner_unsupported),
+            ("extended attributes", copy_xattrs(src, dest), &self.xattrs_unsupported),
+        ];
+        for (what, result, reported) in results {
+            match result {
+                Ok(()) => {}
+                Err(e) if matches!(e.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::Unsupported) => {
+                    if !reported.swap(true, Ordering::Relaxed) {
+                        add_warning(format!("could not keep the {} of copies: {}; continuing without", what, e));
+                    }
+                }
+                Err(e) => add_warning(format!("could not keep the {}: {}", what, e)),
+            }
+        }
+    }
+
+    /// `--diff`: how placing `path` into `dest_dir` compares with the file
+    /// already there under its name. `None` for files that won't be placed.
+    fn change(&self, path: &Path, dest_dir: &Path, status: &ActionStatus) -> Option<Change> {
+        match status {
+            ActionStatus::Skipped(SkipReason::Identical) => return Some(Change::Duplicate),
+            ActionStatus::Skipped(SkipReason::Exists | SkipReason::NotNewer) => {}
+            status if status.is_placed() => {}
+            _ => return None,
+        }
+        let existing = dest_dir.join(self.dest_name(path));
+        if !existing.is_file() {
+            return Some(Change::New);
+        }
+        match files_identical(path, &existing) {
+            Ok(true) => Some(Change::Duplicate),
+            _ => Some(Change::Conflict),
+        }
+    }
+
+    /// Name `src` gets at its destination: its own, or the one `--rename` gives
+    /// it, NFC-normalized with `--normalize-unicode` and made safe for Windows
+    /// there (see [`windows_safe_name`]).
+    fn dest_name(&self, src: &Path) -> PathBuf {
+        let file_name = src.file_name().expect("file should have a name");
+        let Some(name) = file_name.to_str() else {
+            return PathBuf::from(file_name);
+        };
+        let name = if self.opts.rename.is_empty() {
+            name.to_string()
+        } else {
+            apply_rules(name, &self.opts.rename)
+        };
+        let name = if self.opts.normalize_unicode { name.nfc().collect() } else { name };
+        if cfg!(windows) {
+            PathBuf::from(windows_safe_name(&name))
+        } else {
+            PathBuf::from(name)
+        }
+    }
+
+    /// Summary statistics for `root`: every file that a run would handle is
+    /// classified and tallied, keeping the `top` largest. Nothing is copied
+    /// or moved.
+    pub fn stats(self, root: &Path, top: usize) -> io::Result<FolderStats> {
+        let mut stats = FolderStats::new(self.opts);
+        self.process_directory(root, 0, &mut vec![root.to_path_buf()], &mut |path| {
+            let meta = fs::metadata(&path);
+            match (self.skip_reason(&path, &meta), meta) {
+                (Err(failure), _) => self.record(FileAction::failed(path, failure)),
+                (Ok(Some(_)), _) => stats.skipped += 1,
+                (Ok(None), Err(e)) => self.record(FileAction::failed(path, Failure::io("could not read metadata", &e))),
+                (Ok(None), Ok(meta)) => {
+                    let Classification { ext, category, .. } = self.classify(&path);
+                    stats.add(path, ext, category, meta.len(), top);
+                }
+            }
+        })?;
+
+        // Entries the walk itself skipped or failed on were recorded in the summary
+        let summary = self.summary.into_inner().unwrap();
+        stats.skipped += summary.count("Skipped");
+        stats.failures = summary
+            .actions
+            .into_iter()
+            .filter(|a| matches!(a.status, ActionStatus::Failed(_)))
+            .collect();
+        Ok(stats)
+    }
+
+    /// Applies `--skip-hidden`, `.organizerignore`, `--include` / `--exclude`, the size limits
+    /// and `--since` / `--until` / `--since-last-run`. The last ones need `meta`;
+    /// failing to read it is only an error when they are set.
+    fn skip_reason(&self, path: &Path, meta: &io::Result<fs::Metadata>) -> Result<Option<SkipReason>, Failure> {
+        let opts = self.opts;
+        if opts.hidden == HiddenFiles::Skip && is_hidden(path) {
+            return Ok(Some(SkipReason::Hidden));
+        }
+        if let Some(reason) = name_filter(path, opts) {
+            return Ok(Some(reason));
+        }
+        if opts.skip_empty || opts.min_size.is_some() || opts.max_size.is_some() {
+            let len = match meta {
+                Ok(meta) => meta.len(),
+                Err(e) => return Err(Failure::io("could not read metadata", e)),
+            };
+            if opts.skip_empty && len == 0 {
+                return Ok(Some(SkipReason::Empty));
+            }
+            if opts.min_size.is_some_and(|min| len < min) {
+                return Ok(Some(SkipReason::TooSmall));
+            }
+            if opts.max_size.is_some_and(|max| len > max) {
+                return Ok(Some(SkipReason::TooLarge));
+            }
+        }
+        if opts.modified_since.is_some() || opts.modified_until.is_some() || self.last_run.is_some() {
+            let modified = match meta.as_ref().map(|meta| meta.modified()) {
+                Ok(Ok(modified)) => modified,
+                Ok(Err(e)) => return Err(Failure::io("could not read modified time", &e)),
+                Err(e) => return Err(Failure::io("could not read metadata", e)),
+            };
+            if opts.modified_since.is_some_and(|since| modified < since) {
+                return Ok(Some(SkipReason::TooOld));
+            }
+            if opts.modified_until.is_some_and(|until| modified > until) {
+                return Ok(Some(SkipReason::TooNew));
+            }
+            if self.last_run.is_some_and(|last| modified < last) {
+                return Ok(Some(SkipReason::Unchanged));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Works out which category `path` belongs in, from its extension or,
+    /// with `--by-mime` / `--sniff-content`, from its contents.
+    fn classify(&self, path: &Path) -> Classification {
+        let opts = self.opts;
+        // A followed symlink is sorted by what it points to, but keeps its own name
+        let target = if opts.follow_symlinks && path.is_symlink() {
+            fs::canonicalize(path).ok()
+        } else {
+            None
+        };
+        let ext = file_extension(target.as_deref().unwrap_or(path), opts.case_sensitive);
+        // The user's own rules know best
+        if let Some(category) = rules::category_for(&opts.rules, target.as_deref().unwrap_or(path), &ext) {
+            let lookup_ext = ext.clone();
+            return Classification {
+                ext,
+                category: category.to_string(),
+                content_type: None,
+                lookup_ext,
+            };
+        }
+        let mut category = self.extensions.category(&ext).to_string();
+        let mut content_type = None;
+        let mut lookup_ext = ext.clone();
+        // Size goes before type: a 2 KB PNG is a small file first
+        let small = opts
+            .small_files
+            .is_some_and(|threshold| fs::metadata(path).is_ok_and(|m| m.len() < threshold));
+        if opts.hidden == HiddenFiles::Separate && is_hidden(path) {
+            category = HIDDEN_CATEGORY.to_string();
+        } else if small {
+            category = SMALL_FILES_CATEGORY.to_string();
+        } else if opts.by_mime
+            && let Ok(Some(sig)) = sniff_type(path)
+            && let Some(by_mime) = category_for_mime(sig.mime, &opts.categories)
+            // Office documents, e-books and the like are ZIP files inside; their extension knows better
+            && !(sig.ext == "zip" && category != "Others")
+        {
+            category = by_mime.to_string();
+            content_type = Some(sig.mime.to_string());
+            lookup_ext = sig.ext.to_string();
+        }
+        // Only unknown extensions are second-guessed: a .docx is a zip inside, but belongs in Documents
+        if opts.sniff_content
+            && !small
+            && category == "Others"
+            && let Ok(Some(sniffed)) = sniff_extension(path)
+        {
+            category = self.extensions.category(sniffed).to_string();
+            content_type = Some(sniffed.to_string());
+            lookup_ext = sniffed.to_string();
+        }
+        Classification {
+            ext,
+            category,
+            content_type,
+            lookup_ext,
+        }
+    }
+
+    /// Copies/moves `path` into `dest_dir` after the dedup, conflict and
+    /// confirmation checks, returning the outcome, where the file ended up and
+    /// how a name collision was resolved. `shown` is the name the file goes
+    /// by in prompts and as the original of duplicates.
+    fn place(
+        &self,
+        path: &Path,
+        shown: &Path,
+        category: &str,
+        dest_dir: &Path,
+        add_warning: &mut dyn FnMut(String),
+    ) -> Placement {
+        let opts = self.opts;
+
+        // Claimed up front so a concurrent copy of the same contents is
+        // recognized as a duplicate; released again if placing fails.
+        let mut content_key = None;
+        if opts.dedup {
+            let hash = match file_sha256(path) {
+                Ok(hash) => hash,
+                Err(e) => return (ActionStatus::Failed(Failure::io("could not hash file", &e)), None, None),
+            };
+            let key = (category.to_string(), hash);
+            let mut seen = self.seen_hashes.lock().unwrap();
+            if let Some(original) = seen.get(&key) {
+                return (ActionStatus::Duplicate(original.clone()), None, None);
+            }
+            seen.insert(key.clone(), shown.to_path_buf());
+            content_key = Some(key);
+        }
+        let forget_hash = || {
+            if let Some(key) = &content_key {
+                self.seen_hashes.lock().unwrap().remove(key);
+            }
+        };
+
+        let (mut dest_path, mut conflict) = match self.claim_destination(path, dest_dir) {
+            Ok(claimed) => claimed,
+            Err(reason) => {
+                forget_hash();
+                return (ActionStatus::Skipped(reason), None, None);
+            }
+        };
+        let release = |dest: &Path| {
+            forget_hash();
+            self.reserved.lock().unwrap().remove(dest);
+        };
+
+        // Other workers may have taken the last slots of --limit meanwhile
+        if opts
+            .limit
+            .is_some_and(|limit| self.acted.fetch_add(1, Ordering::SeqCst) >= limit)
+        {
+            release(&dest_path);
+            self.stopped.store(true, Ordering::Relaxed);
+            return (ActionStatus::Skipped(SkipReason::LimitReached), None, None);
+        }
+        let check_limit = || {
+            if opts
+                .limit
+                .is_some_and(|limit| self.acted.load(Ordering::SeqCst) >= limit)
+            {
+                self.stopped.store(true, Ordering::Relaxed);
+            }
+        };
+
+        if opts.dry_run {
+            let status = if opts.move_files {
+                ActionStatus::WouldMove
+            } else {
+                ActionStatus::WouldCopy
+            };
+            check_limit();
+            return (status, Some(dest_path), conflict);
+        }
+
+        let planned = PlannedAction {
+            source: shown,
+            dest_dir,
+            category,
+        };
+        let decision = {
+            let mut confirm = self.confirm.lock().unwrap();
+            (*confirm)(&planned)
+        };
+        match decision {
+            Decision::Proceed => {}
+            Decision::Skip => {
+                release(&dest_path);
+                if opts.limit.is_some() {
+                    self.acted.fetch_sub(1, Ordering::SeqCst);
+                }
+                return (ActionStatus::Skipped(SkipReason::Declined), None, None);
+            }
+            Decision::Stop => {
+                release(&dest_path);
+                if opts.limit.is_some() {
+                    self.acted.fetch_sub(1, Ordering::SeqCst);
+                }
+                self.stopped.store(true, Ordering::Relaxed);
+                return (ActionStatus::Skipped(SkipReason::Stopped), None, None);
+            }
+        }
+
+        let result = self
+            .create_dir(dest_dir)
+            .and_then(|()| self.transfer(path, dest_dir, &mut dest_path, &mut conflict, add_warning));
+        check_limit();
+
+        match result {
+            Ok(()) => {
+                let status = if opts.move_files {
+                    ActionStatus::Moved
+                } else {
+                    ActionStatus::Copied
+                };
+                (status, Some(dest_path), conflict)
+            }
+            Err(e) => {
+                release(&dest_path);
+                let e = explain_path_error(e, &dest_path);
+                let failure = Failure {
+                    kind: FailureKind::of(&e),
+                    message: e.to_string(),
+                };
+                (ActionStatus::Failed(failure), None, None)
+            }
+        }
+    }
+
+    /// Creates `dir` and the folders above it that don't exist yet, noting
+    /// them down for [`remove_unused_dirs`].
+    fn create_dir(&self, dir: &Path) -> io::Result<()> {
+        let missing = dir.ancestors().take_while(|d| !d.as_os_str().is_empty() && !d.exists());
+        self.created_dirs.lock().unwrap().extend(missing.map(Path::to_path_buf));
+        create_dir_all(dir)
+    }
+
+    /// Copies or moves `path` to `dest`. A copy claims its name by creating the
+    /// file exclusively: if another program (or another run) created a file
+    /// there since the name was picked, the next free name is claimed instead
+    /// and `dest` and `conflict` are updated to match. With
+    /// `--trash-on-overwrite` a file about to be replaced is trashed first.
+    /// Copying or moving is tried again `--retries` times on transient errors.
+    fn transfer(
+        &self,
+        path: &Path,
+        dest_dir: &Path,
+        dest: &mut PathBuf,
+        conflict: &mut Option<Conflict>,
+        add_warning: &mut dyn FnMut(String),
+    ) -> io::Result<()> {
+        if *conflict == Some(Conflict::Overwritten) && self.opts.trash_on_overwrite {
+            let trashed = trash_file(dest, self.out_root).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("could not move the replaced file to the trash: {}", e),
+                )
+            })?;
+            if let Trashed::Fallback(_, reason) = trashed
+                && !self.trash_fallback_reported.swap(true, Ordering::Relaxed)
+            {
+                add_warning(format!(
+                    "could not use the system trash ({}); replaced files go to '{}'",
+                    reason,
+                    self.out_root.join(FALLBACK_TRASH_DIR).display()
+                ));
+            }
+        }
+        let retries = self.opts.retries;
+        let moving = self.opts.move_files && !path.is_symlink();
+        if moving && *conflict == Some(Conflict::Overwritten) {
+            return with_retries(retries, || move_file(path, dest));
+        }
+        // Each fails if another process created the file since its name was picked
+        let place = |dest: &Path| match (moving, self.opts.link) {
+            (true, _) => move_new(path, dest),
+            (false, Some(kind)) => link_new(path, dest, kind),
+            (false, None) => copy_new(path, dest),
+        };
+        if *conflict == Some(Conflict::Overwritten) {
+            match self.opts.link {
+                Some(_) => {
+                    // Already gone if it went to the trash
+                    if let Err(e) = fs::remove_file(&*dest)
+                        && e.kind() != io::ErrorKind::NotFound
+                    {
+                        return Err(e);
+                    }
+                    with_retries(retries, || place(dest))?;
+                }
+                None => {
+                    with_retries(retries, || fs::copy(path, &*dest))?;
+                }
+            }
+        } else {
+            loop {
+                match with_retries(retries, || place(dest)) {
+                    Ok(()) => break,
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                        // The name that was taken stays reserved, so it isn't handed out again
+                        let Ok((next, next_conflict)) = self.claim_destination(path, dest_dir) else {
+                            return Err(e);
+                        };
+                        *dest = next;
+                        *conflict = next_conflict;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        if moving {
+            return Ok(());
+        }
+        if self.opts.verify && !self.opts.move_files && self.opts.link.is_none() {
+            verify_copy(path, dest)?;
+        }
+        if self.opts.move_files {
+            // Moving the link itself could leave it dangling, so the contents
+            // are copied and only the link is removed; the target stays put
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Picks the destination for `src` in `dest_dir` according to
+    /// `--on-conflict` and reserves it so no other worker is given the same
+    /// name. Names taken by other files of this run are always renamed, so
+    /// `overwrite` and `newest` only ever replace files that were there before. The name
+    /// is the one `--rename` gives the file, if any.
+    fn claim_destination(&self, src: &Path, dest_dir: &Path) -> Result<(PathBuf, Option<Conflict>), SkipReason> {
+        let name = self.dest_name(src);
+        let plain = dest_dir.join(&name);
+        let mut reserved = self.reserved.lock().unwrap();
+        let on_disk = plain.exists();
+        if !on_disk && !reserved.contains(&plain) {
+            reserved.insert(plain.clone());
+            return Ok((plain, None));
+        }
+
+        match self.opts.on_conflict {
+            ConflictPolicy::Skip => Err(SkipReason::Exists),
+            ConflictPolicy::Overwrite | ConflictPolicy::Newest if !reserved.contains(&plain) => {
+                // Only a source known to be newer replaces the file there
+                if self.opts.on_conflict == ConflictPolicy::Newest && !is_newer(src, &plain) {
+                    return Err(SkipReason::NotNewer);
+                }
+                reserved.insert(plain.clone());
+                drop(reserved);
+                if files_identical(src, &plain).unwrap_or(false) {
+                    self.reserved.lock().unwrap().remove(&plain);
+                    return Err(SkipReason::Identical);
+                }
+                Ok((plain, Some(Conflict::Overwritten)))
+            }
+            _ => {
+                let format = &self.opts.conflict_format;
+                let dest =
+                    unique_destination_with_format(&name, dest_dir, format, |p| reserved.contains(p) || p.exists());
+                reserved.insert(dest.clone());
+                Ok((dest, Some(Conflict::Renamed)))
+            }
+        }
+    }
+}
+
+/// Whether `src` was modified later than `existing`; `false` if either time
+/// can't be read.
+fn is_newer(src: &Path, existing: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified());
+    match (modified(src), modified(existing)) {
+        (Ok(src), Ok(existing)) => src > existing,
+        _ => false,
+    }
+}
+
+/// Removes the subfolders of `dir` that are empty after a move, deepest
+/// first. Output folders, symlinks and `dir` itself are left alone.
+fn remove_empty_dirs(dir: &Path, out_root: &Path, opts: &Options, removed: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_dir = entry.file_type().is_ok_and(|ft| ft.is_dir());
+        if !is_dir || is_output_dir(&path, out_root, opts) {
+            continue;
+        }
+        remove_empty_dirs(&path, out_root, opts, removed);
+        // Fails if anything is left inside, which is exactly when it should stay
+        if fs::remove_dir(&path).is_ok() {
+            removed.push(path);
+        }
+    }
+}
+
+/// Removes the subfolders of `root` that files were moved out of, and their
+/// parents, if nothing is left in them now, deepest first. Folders that were
+/// empty before the run aren't touched.
+fn remove_emptied_dirs(root: &Path, actions: &[FileAction]) -> Vec<PathBuf> {
+    let mut emptied: Vec<&Path> = actions
+        .iter()
+        .filter(|a| a.status == ActionStatus::Moved)
+        .flat_map(|a| a.source.ancestors().skip(1).take_while(|dir| *dir != root && dir.starts_with(root)))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    emptied.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    // Fails if anything is left inside, which is exactly when it should stay
+    emptied.into_iter().filter(|dir| fs::remove_dir(dir).is_ok()).map(Path::to_path_buf).collect()
+}
+
+/// Removes the folders in `created` that are still empty, deepest first, so
+/// a file that failed to copy doesn't leave an empty category folder behind.
+fn remove_unused_dirs(created: &mut HashSet<PathBuf>) {
+    let mut dirs: Vec<PathBuf> = created.drain().collect();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    for dir in dirs {
+        // Fails if anything landed inside, which is exactly when it should stay
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+/// `--transactional`: moves every file `summary` says was moved back to where
+/// it came from, newest first, and removes the folders in `out_root` that
+/// leaves empty. A file that can't be moved back stays where it is and gets a
+/// warning. Returns the summary recounted to match; the counts of actions it
+/// didn't keep (see [`Options::keep_actions`]) stay as they are.
+pub(crate) fn roll_back(mut summary: Summary, out_root: &Path) -> Summary {
+    let mut actions = std::mem::take(&mut summary.actions);
+    for action in actions.iter_mut().rev() {
+        let Some(dest) = action
+            .destination
+            .clone()
+            .filter(|_| action.status == ActionStatus::Moved)
+        else {
+            continue;
+        };
+        summary.untally(action);
+        match move_back(&dest, &action.source) {
+            Ok(()) => {
+                for dir in dest
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|dir| *dir != out_root && dir.starts_with(out_root))
+                {
+                    if fs::remove_dir(dir).is_err() {
+                        break;
+                    }
+                }
+                action.status = ActionStatus::Skipped(SkipReason::RolledBack);
+                action.destination = None;
+                action.conflict = None;
+            }
+            Err(e) => {
+                let problem = format!("could not be moved back to '{}': {}", action.source.display(), e);
+                action.warning = Some(match action.warning.take() {
+                    Some(earlier) => format!("{}; {}", earlier, problem),
+                    None => problem,
+                });
+            }
+        }
+        summary.tally(action);
+    }
+    summary.actions = actions;
+    summary
+}
+
+/// Moves `dest` back to `source`, unless something took its place meanwhile.
+fn move_back(dest: &Path, source: &Path) -> io::Result<()> {
+    if source.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "something else is there now",
+        ));
+    }
+    if let Some(parent) = source.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_file(dest, source)
+}
+
+/// `--verify`: compares the copy at `dest` with `src`, copying once more if
+/// they differ. A copy that still doesn't match is removed.
+fn verify_copy(src: &Path, dest: &Path) -> io::Result<()> {
+    if files_identical(src, dest)? {
+        return Ok(());
+    }
+    fs::copy(src, dest)?;
+    if files_identical(src, dest)? {
+        return Ok(());
+    }
+    let _ = fs::remove_file(dest);
+    Err(io::Error::other(
+        "the copy doesn't match the original, even after copying it again",
+    ))
+}
+
+/// Applies `.organizerignore` and `--include` / `--exclude` to the file name.
+/// Excludes win over includes.
+fn name_filter(path: &Path, opts: &Options) -> Option<SkipReason> {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if let Some(pattern) = opts.ignore.iter().find(|p| p.matches(name)) {
+        return Some(SkipReason::Ignored(pattern.as_str().to_string()));
+    }
+    if let Some(pattern) = opts.exclude.iter().find(|p| p.matches(name)) {
+        return Some(SkipReason::Excluded(pattern.as_str().to_string()));
+    }
+    if !opts.include.is_empty() && !opts.include.iter().any(|p| p.matches(name)) {
+        return Some(SkipReason::NotIncluded);
+    }
+    None
+}
+
+/// True if `path` is one of the folders `opts.destinations` sends a category to.
+fn is_destination(path: &Path, out_root: &Path, opts: &Options) -> bool {
+    opts.destinations.values().any(|dest| {
+        let dest = out_root.join(dest);
+        dest == path || fs::canonicalize(&dest).is_ok_and(|dest| dest == path)
+    })
+}
+
+/// True if `path` is the output root or one of the folders this tool writes
+/// into it (see [`is_category_dir`]). With `--by-date --date-only` the top-level folders
+/// are dates instead, recognized by being all digits (years) or `Unknown-Date`.
+fn is_output_dir(path: &Path, out_root: &Path, opts: &Options) -> bool {
+    if path == out_root || is_destination(path, out_root, opts) {
+        return true;
+    }
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if path.parent() == Some(out_root) {
+        if name == FALLBACK_TRASH_DIR {
+            return true;
+        }
+        if opts.by_date && opts.date_only {
+            return name == UNKNOWN_DATE_FOLDER || (!name.is_empty() && name.chars().all(|c| c.is_ascii_digit()));
+        }
+    }
+    is_category_dir(path, out_root, opts)
+}
@@ -0,0 +1,92 @@
+//! `--emit-sh`: turns a dry run into a shell script doing the same copies,
+//! moves or links, to read, edit and run by hand:
+//!
+//! ```text
+//! #!/bin/sh
+//! mkdir -p '/home/me/Downloads/Images'
+//! cp -n '/home/me/Downloads/cat.jpg' '/home/me/Downloads/Images/cat.jpg'
+//! ```
+//!
+//! Only the placing of files is scripted; hooks, logs and the removal of
+//! emptied folders are left to the tool itself. The targets are the names
+//! the dry run picked, so a file is never put over another one unless the
+//! run would have overwritten it too (`--on-conflict overwrite`).
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::Options;
+use crate::action::{Change, Conflict, FileAction};
+use crate::exec::sh_quote;
+use crate::transfer::LinkKind;
+
+/// The script placing every file `actions` would place the way `opts` says
+/// (copying, moving or linking, keeping timestamps or not).
+pub fn shell_script(actions: &[FileAction], opts: &Options) -> String {
+    let keep_times = opts.preserve_times || opts.preserve_metadata;
+    let mut script = String::from("#!/bin/sh\n");
+    let mut made = HashSet::new();
+    for action in actions.iter().filter(|a| a.status.is_placed() && a.change != Some(Change::Duplicate)) {
+        let Some(dest) = action.destination.as_deref() else { continue };
+        if let Some(dir) = dest.parent()
+            && made.insert(dir)
+        {
+            script.push_str(&format!("mkdir -p {}\n", quote(dir)));
+        }
+        // -n keeps whatever got there since the dry run
+        let overwrite = action.conflict == Some(Conflict::Overwritten);
+        let command = match (opts.move_files, opts.link, overwrite) {
+            (true, _, false) => "mv -n",
+            (true, _, true) => "mv -f",
+            (false, Some(LinkKind::Hard), false) => "ln",
+            (false, Some(LinkKind::Hard), true) => "ln -f",
+            (false, Some(LinkKind::Symbolic), false) => "ln -s",
+            (false, Some(LinkKind::Symbolic), true) => "ln -sf",
+            (false, None, false) if keep_times => "cp -pn",
+            (false, None, false) => "cp -n",
+            (false, None, true) if keep_times => "cp -pf",
+            (false, None, true) => "cp -f",
+        };
+        script.push_str(&format!("{} {} {}\n", command, quote(&action.source), quote(dest)));
+    }
+    script
+}
+
+fn quote(path: &Path) -> String {
+    sh_quote(&path.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::{ActionStatus, SkipReason};
+    use std::path::PathBuf;
+
+    fn placed(source: &str, dest: &str) -> FileAction {
+        FileAction {
+            destination: Some(PathBuf::from(dest)),
+            status: ActionStatus::WouldCopy,
+            ..FileAction::skipped(PathBuf::from(source), SkipReason::Hidden)
+        }
+    }
+
+    #[test]
+    fn placed_files_become_quoted_commands() {
+        let mut overwriting = placed("/in/b.png", "/out/Images/b.png");
+        overwriting.conflict = Some(Conflict::Overwritten);
+        let actions = [
+            placed("/in/it's.jpg", "/out/Images/it's.jpg"),
+            overwriting,
+            FileAction::skipped(PathBuf::from("/in/.hidden"), SkipReason::Hidden),
+        ];
+        assert_eq!(
+            shell_script(&actions, &Options::default()),
+            "#!/bin/sh\nmkdir -p '/out/Images'\ncp -n '/in/it'\\''s.jpg' '/out/Images/it'\\''s.jpg'\n\
+             cp -f '/in/b.png' '/out/Images/b.png'\n"
+        );
+        let moving = Options { move_files: true, ..Options::default() };
+        assert!(shell_script(&actions, &moving).contains("\nmv -n '/in/it"));
+        let linking = Options { link: Some(LinkKind::Symbolic), ..Options::default() };
+        assert!(shell_script(&actions, &linking).contains("\nln -sf '/in/b.png'"));
+    }
+}
@@ -0,0 +1,87 @@
+//! Human-friendly file sizes.
+
+/// Parses sizes like `500`, `500K`, `10M`, `2G` or `1.5GiB` into bytes.
+///
+/// Suffixes are case-insensitive. `K`/`M`/`G`/`T` and `KiB`/`MiB`/... are
+/// powers of 1024; `KB`/`MB`/`GB`/`TB` are powers of 1000; `B` or no suffix
+/// means bytes.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let s = input.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(split);
+    if number.is_empty() {
+        return Err(format!("invalid size '{}': expected a number like 500K or 10M", input));
+    }
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}': '{}' is not a number", input, number))?;
+
+    let multiplier: u64 = match suffix.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        other => return Err(format!("invalid size '{}': unknown unit '{}'", input, other)),
+    };
+
+    let bytes = value * multiplier as f64;
+    // u64::MAX rounds up to 2^64 as a float, which doesn't fit
+    if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+        return Err(format!("invalid size '{}': too large", input));
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// Formats a byte count for display, e.g. `512 B`, `3.2 KiB` or `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    // Compared after rounding, so just under 1 MiB shows as 1.0 MiB and not 1024.0 KiB
+    while value >= 1023.95 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_parse_in_binary_and_decimal_units() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size(" 10k "), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5GiB"), Ok(3 << 29));
+        assert_eq!(parse_size("2 MB"), Ok(2_000_000));
+        assert_eq!(parse_size("1KB"), Ok(1_000));
+        assert_eq!(parse_size("0B"), Ok(0));
+        assert_eq!(parse_size("15t"), Ok(15 << 40));
+        for bad in ["", "K", "-5", "1.2.3", "10 parsecs", "16777216T"] {
+            assert!(parse_size(bad).is_err(), "{:?} was accepted", bad);
+        }
+    }
+
+    #[test]
+    fn sizes_format_with_the_largest_fitting_unit() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size((1 << 20) - 1), "1.0 MiB");
+        assert_eq!(format_size(1 << 30), "1.0 GiB");
+        assert_eq!(format_size(u64::MAX), "16384.0 PiB");
+        for size in ["1K", "2.5M", "3G", "7T"] {
+            assert_eq!(parse_size(&format_size(parse_size(size).unwrap()).replace(' ', "")), parse_size(size));
+        }
+    }
+}
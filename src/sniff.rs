@@ -0,0 +1,79 @@
+//! Content sniffing for `--sniff-content`: recognizes common file types by
+//! the magic number at the start of the file, for files whose extension is
+//! missing or unknown.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How many bytes of a file are looked at.
+const HEADER_LEN: usize = 16;
+
+/// A file type recognized from the contents of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    /// The extension files of this type usually have, e.g. `"png"`.
+    pub ext: &'static str,
+    /// MIME type, e.g. `"image/png"`.
+    pub mime: &'static str,
+}
+
+/// Known signatures: offset, bytes expected there, the extension and MIME
+/// type the contents correspond to. Checked in order, so longer or more
+/// specific signatures come first.
+const SIGNATURES: &[(usize, &[u8], &str, &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "png", "image/png"),
+    (0, b"\xff\xd8\xff", "jpg", "image/jpeg"),
+    (0, b"GIF87a", "gif", "image/gif"),
+    (0, b"GIF89a", "gif", "image/gif"),
+    (8, b"WEBP", "webp", "image/webp"),
+    (0, b"%PDF-", "pdf", "application/pdf"),
+    (0, b"PK\x03\x04", "zip", "application/zip"),
+    (0, b"PK\x05\x06", "zip", "application/zip"),
+    (0, b"Rar!\x1a\x07", "rar", "application/vnd.rar"),
+    (0, b"7z\xbc\xaf\x27\x1c", "7z", "application/x-7z-compressed"),
+    (0, b"\x1f\x8b", "gz", "application/gzip"),
+    (8, b"WAVE", "wav", "audio/wav"),
+    (0, b"ID3", "mp3", "audio/mpeg"),
+    (0, b"fLaC", "flac", "audio/flac"),
+    // An MP4 container holding only audio announces itself with the M4A brand
+    (8, b"M4A ", "m4a", "audio/mp4"),
+    (4, b"ftyp", "mp4", "video/mp4"),
+    (0, b"\x1a\x45\xdf\xa3", "mkv", "video/x-matroska"),
+    (0, b"\x7fELF", "elf", "application/x-executable"),
+];
+
+/// Reads the first few bytes of `path` and returns the extension its contents
+/// look like (e.g. `"png"`), or `None` if no known signature matches.
+pub fn sniff_extension(path: &Path) -> io::Result<Option<&'static str>> {
+    Ok(sniff_type(path)?.map(|sig| sig.ext))
+}
+
+/// Reads the first few bytes of `path` and returns the type its contents
+/// look like, or `None` if no known signature matches.
+pub fn sniff_type(path: &Path) -> io::Result<Option<Signature>> {
+    let mut header = [0u8; HEADER_LEN];
+    let mut len = 0;
+    let mut file = File::open(path)?;
+    // A single read may return less than asked for, even before the end
+    while len < HEADER_LEN {
+        match file.read(&mut header[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(match_type(&header[..len]))
+}
+
+/// Matches a file header against the known signatures.
+pub fn match_signature(header: &[u8]) -> Option<&'static str> {
+    match_type(header).map(|sig| sig.ext)
+}
+
+/// Like [`match_signature`], returning the MIME type too.
+pub fn match_type(header: &[u8]) -> Option<Signature> {
+    SIGNATURES
+        .iter()
+        .find(|(offset, magic, _, _)| header.get(*offset..offset + magic.len()) == Some(*magic))
+        .map(|(_, _, ext, mime)| Signature { ext, mime })
+}
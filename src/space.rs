@@ -0,0 +1,117 @@
+//! Free space checks: whether the files a run will write fit on the drives
+//! they go to, so a copy doesn't stop halfway with a full disk.
+//!
+//! Only Unix can tell how much space is free (`statvfs`); elsewhere the
+//! check finds nothing to complain about.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::FileAction;
+
+/// A drive the planned files won't fit on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortfall {
+    /// One of the folders on that drive the files go to.
+    pub folder: PathBuf,
+    /// Bytes the run will write there.
+    pub needed: u64,
+    /// Bytes free there now.
+    pub available: u64,
+}
+
+/// Bytes free for unprivileged users on the drive holding `path`, or its
+/// nearest existing parent (destination folders are created later).
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = existing_ancestor(path)?;
+    let c_path = CString::new(existing.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `c_path` is a valid C string and `stats` is only read once statvfs filled it
+    if unsafe { libc::statvfs(c_path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stats = unsafe { stats.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "free space can't be checked on this platform"))
+}
+
+/// The drives that the files `actions` place won't fit on. With `moving`,
+/// files that stay on their own drive take up no extra space.
+pub fn check_space(actions: &[FileAction], moving: bool) -> Vec<Shortfall> {
+    let mut needs = SpaceNeeds::new(moving);
+    actions.iter().for_each(|action| needs.add(action));
+    needs.shortfalls()
+}
+
+/// The space placed files need on each drive, added up one action at a time
+/// so the actions don't have to be kept around for [`check_space`].
+#[derive(Debug)]
+pub struct SpaceNeeds {
+    moving: bool,
+    /// Drive -> (a folder on it, bytes needed)
+    needed: HashMap<u64, (PathBuf, u64)>,
+}
+
+impl SpaceNeeds {
+    /// With `moving`, files that stay on their own drive take up no extra space.
+    pub fn new(moving: bool) -> Self {
+        SpaceNeeds { moving, needed: HashMap::new() }
+    }
+
+    /// Counts the file `action` places, if it places one.
+    pub fn add(&mut self, action: &FileAction) {
+        if !action.status.is_placed() {
+            return;
+        }
+        let Some(folder) = action.destination.as_deref().and_then(Path::parent) else { return };
+        let Ok(drive) = drive_of(folder) else { return };
+        if self.moving && drive_of(&action.source).is_ok_and(|source| source == drive) {
+            return;
+        }
+        self.needed.entry(drive).or_insert_with(|| (folder.to_path_buf(), 0)).1 += action.size.unwrap_or(0);
+    }
+
+    /// The drives the files counted so far won't fit on.
+    pub fn shortfalls(self) -> Vec<Shortfall> {
+        let mut short: Vec<Shortfall> = self
+            .needed
+            .into_values()
+            .filter_map(|(folder, needed)| {
+                let available = available_space(&folder).ok()?;
+                (needed > available).then_some(Shortfall { folder, needed, available })
+            })
+            .collect();
+        short.sort_by(|a, b| a.folder.cmp(&b.folder));
+        short
+    }
+}
+
+/// Device id of the drive holding `path` (or its nearest existing parent).
+#[cfg(unix)]
+fn drive_of(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(std::fs::metadata(existing_ancestor(path)?)?.dev())
+}
+
+#[cfg(not(unix))]
+fn drive_of(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "drives can't be told apart on this platform"))
+}
+
+#[cfg(unix)]
+fn existing_ancestor(path: &Path) -> io::Result<&Path> {
+    path.ancestors()
+        .find(|dir| !dir.as_os_str().is_empty() && dir.exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("'{}' doesn't exist", path.display())))
+}
@@ -0,0 +1,72 @@
+//! `--since-last-run`: when a run over a folder ends without errors, the
+//! time it started is saved in [`STATE_FILE`] in the output folder, and the
+//! next run with the flag only looks at files modified after that.
+//!
+//! The file holds a time per organized folder, so folders sharing one
+//! `--output` don't hide each other's new files:
+//!
+//! ```text
+//! {"/home/me/Downloads":"2024-06-01T12:00:00+02:00"}
+//! ```
+//!
+//! A missing or unreadable file just means everything is looked at.
+
+use chrono::{DateTime, Local};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Name of the state file, in the output folder.
+pub const STATE_FILE: &str = ".organizer-state.json";
+
+/// When the last successful run over `folder` into `out_root` started.
+pub fn last_run(out_root: &Path, folder: &Path) -> Option<SystemTime> {
+    let runs = read_state(out_root)?;
+    let time = DateTime::parse_from_rfc3339(runs.get(&*folder.to_string_lossy())?).ok()?;
+    Some(time.into())
+}
+
+/// Records that a run over `folder` into `out_root` started at `started`
+/// and went well, keeping the times of other folders.
+pub fn save_last_run(out_root: &Path, folder: &Path, started: SystemTime) -> io::Result<()> {
+    let mut runs = read_state(out_root).unwrap_or_default();
+    runs.insert(folder.to_string_lossy().into_owned(), DateTime::<Local>::from(started).to_rfc3339());
+    let text = serde_json::to_string(&runs).map_err(io::Error::other)?;
+    // Written next to it and renamed over it, so an interrupted write can't leave half a file
+    let path = out_root.join(STATE_FILE);
+    let partial = out_root.join(format!("{}.partial", STATE_FILE));
+    fs::write(&partial, text)?;
+    fs::rename(&partial, &path)
+}
+
+/// Folder -> RFC 3339 time, or `None` if there is no readable state file.
+fn read_state(out_root: &Path) -> Option<BTreeMap<String, String>> {
+    let text = fs::read_to_string(out_root.join(STATE_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn times_are_kept_per_folder_and_bad_state_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (Path::new("/in/a"), Path::new("/in/b"));
+        assert_eq!(last_run(dir.path(), a), None);
+
+        let started = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        save_last_run(dir.path(), a, started).unwrap();
+        save_last_run(dir.path(), b, started + Duration::from_secs(60)).unwrap();
+        assert_eq!(last_run(dir.path(), a), Some(started));
+        assert_eq!(last_run(dir.path(), b), Some(started + Duration::from_secs(60)));
+
+        fs::write(dir.path().join(STATE_FILE), "{not json").unwrap();
+        assert_eq!(last_run(dir.path(), a), None);
+        save_last_run(dir.path(), a, started).unwrap();
+        assert_eq!(last_run(dir.path(), a), Some(started));
+    }
+}
@@ -0,0 +1,162 @@
+//! `--stats-only`, `--extension-report` and `--report-only-others`: what a
+//! folder is made of, per category and per extension, without organizing
+//! anything.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::{FileAction, HIDDEN_CATEGORY, HiddenFiles, Options, SMALL_FILES_CATEGORY, display_order};
+
+/// One of the largest files found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LargeFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub category: String,
+}
+
+/// Files with one extension.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtensionCount {
+    pub files: usize,
+    pub bytes: u64,
+    /// Category the extension's files go to (the first one seen, should
+    /// `--sniff-content` put some elsewhere).
+    pub category: String,
+}
+
+/// A file no category matched, for `--report-only-others`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmatchedFile {
+    pub path: PathBuf,
+    /// Its extension ("" for none).
+    pub ext: String,
+    pub size: u64,
+}
+
+/// Result of [`crate::folder_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FolderStats {
+    /// Files per category.
+    pub counts: HashMap<String, usize>,
+    /// Bytes per category.
+    pub bytes: HashMap<String, u64>,
+    /// Files per extension ("" for none), as [`file_extension`](crate::file_extension) gives it.
+    pub extensions: HashMap<String, ExtensionCount>,
+    /// The largest files, biggest first.
+    pub largest: Vec<LargeFile>,
+    /// The files that go to "Others", in the order they were found.
+    pub others: Vec<UnmatchedFile>,
+    /// Entries left out by the filters (`--include`, `--skip-hidden`, ...).
+    pub skipped: usize,
+    /// Entries that couldn't be looked at.
+    pub failures: Vec<FileAction>,
+}
+
+impl FolderStats {
+    pub(crate) fn new(options: &Options) -> Self {
+        let mut counts: HashMap<String, usize> = options.categories.keys().map(|k| (k.clone(), 0)).collect();
+        counts.insert("Others".to_string(), 0);
+        if options.hidden == HiddenFiles::Separate {
+            counts.insert(HIDDEN_CATEGORY.to_string(), 0);
+        }
+        if options.small_files.is_some() {
+            counts.insert(SMALL_FILES_CATEGORY.to_string(), 0);
+        }
+        let bytes = counts.keys().map(|k| (k.clone(), 0)).collect();
+        FolderStats { counts, bytes, ..FolderStats::default() }
+    }
+
+    /// Counts a file, keeping it among the `top` largest if it's big enough.
+    pub(crate) fn add(&mut self, path: PathBuf, ext: String, category: String, size: u64, top: usize) {
+        *self.counts.entry(category.clone()).or_insert(0) += 1;
+        *self.bytes.entry(category.clone()).or_insert(0) += size;
+        if category == "Others" {
+            self.others.push(UnmatchedFile { path: path.clone(), ext: ext.clone(), size });
+        }
+        let by_ext = self.extensions.entry(ext).or_insert_with(|| ExtensionCount {
+            category: category.clone(),
+            ..ExtensionCount::default()
+        });
+        by_ext.files += 1;
+        by_ext.bytes += size;
+        if self.largest.len() < top || self.largest.last().is_some_and(|smallest| size > smallest.size) {
+            let at = self.largest.partition_point(|f| f.size >= size);
+            self.largest.insert(at, LargeFile { path, size, category });
+            self.largest.truncate(top);
+        }
+    }
+
+    /// Categories in summary order with their file count and size.
+    pub fn rows(&self) -> Vec<(&str, usize, u64)> {
+        display_order(&self.counts)
+            .into_iter()
+            .map(|k| (k, self.counts[k], self.bytes.get(k).copied().unwrap_or_default()))
+            .collect()
+    }
+
+    /// Extensions with their counts, most files first (then alphabetically).
+    pub fn extension_rows(&self) -> Vec<(&str, &ExtensionCount)> {
+        let mut rows: Vec<(&str, &ExtensionCount)> = self.extensions.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        rows.sort_by(|a, b| b.1.files.cmp(&a.1.files).then(a.0.cmp(b.0)));
+        rows
+    }
+
+    /// The files that go to "Others", by extension, then by name (then by
+    /// path, for equal names in different folders).
+    pub fn others_rows(&self) -> Vec<&UnmatchedFile> {
+        let mut rows: Vec<&UnmatchedFile> = self.others.iter().collect();
+        rows.sort_by(|a, b| {
+            a.ext
+                .cmp(&b.ext)
+                .then_with(|| a.path.file_name().cmp(&b.path.file_name()))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        rows
+    }
+
+    /// Number of files counted in all categories together.
+    pub fn total_files(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// Size of all counted files together.
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes.values().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_largest_files_biggest_first() {
+        let mut stats = FolderStats::new(&Options::default());
+        for (name, size) in [("a", 10), ("b", 30), ("c", 20), ("d", 5)] {
+            stats.add(PathBuf::from(name), String::new(), "Others".to_string(), size, 2);
+        }
+        let largest: Vec<u64> = stats.largest.iter().map(|f| f.size).collect();
+        assert_eq!(largest, [30, 20]);
+        assert_eq!(stats.counts["Others"], 4);
+        assert_eq!(stats.total_bytes(), 65);
+        assert_eq!(stats.extensions[""].files, 4);
+    }
+
+    #[test]
+    fn others_are_listed_by_extension_then_name() {
+        let mut stats = FolderStats::new(&Options::default());
+        let files = [
+            ("b/z.xyz", "xyz", "Others"),
+            ("a.jpg", "jpg", "Images"),
+            ("y.abc", "abc", "Others"),
+            ("a/z.xyz", "xyz", "Others"),
+            ("x.xyz", "xyz", "Others"),
+        ];
+        for (path, ext, category) in files {
+            stats.add(PathBuf::from(path), ext.to_string(), category.to_string(), 1, 0);
+        }
+        let rows: Vec<&str> = stats.others_rows().iter().map(|f| f.path.to_str().unwrap()).collect();
+        assert_eq!(rows, ["y.abc", "x.xyz", "a/z.xyz", "b/z.xyz"]);
+    }
+}
@@ -0,0 +1,50 @@
+//! `--tag-pattern REGEX`: files whose names carry a tag, like
+//! `report_[finance].pdf` with `\[(\w+)\]`, go into a folder named after the
+//! tag inside their category (`Documents/finance/report_[finance].pdf`).
+//! The first capture group is the tag; names the pattern doesn't match are
+//! sorted as usual.
+
+use regex::Regex;
+
+/// A checked `--tag-pattern`.
+#[derive(Debug, Clone)]
+pub struct TagPattern(Regex);
+
+impl TagPattern {
+    /// Compiles `pattern`, which needs a capture group for the tag.
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("invalid --tag-pattern '{}': {}", pattern, e))?;
+        if regex.captures_len() < 2 {
+            return Err(format!(
+                "--tag-pattern '{}' needs a capture group for the tag, e.g. '\\[(\\w+)\\]'",
+                pattern
+            ));
+        }
+        Ok(TagPattern(regex))
+    }
+
+    /// The tag in `name`, if the pattern matches and the captured text can
+    /// be a folder name.
+    pub fn tag_of<'a>(&self, name: &'a str) -> Option<&'a str> {
+        let tag = self.0.captures(name)?.get(1)?.as_str().trim();
+        let usable = !tag.is_empty() && tag != "." && tag != ".." && !tag.contains(['/', '\\']);
+        usable.then_some(tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_group_is_the_tag() {
+        let pattern = TagPattern::parse(r"\[([^\]]*)\]").unwrap();
+        assert_eq!(pattern.tag_of("report_[finance].pdf"), Some("finance"));
+        assert_eq!(pattern.tag_of("report.pdf"), None);
+        assert_eq!(pattern.tag_of("empty_[].pdf"), None);
+        assert_eq!(pattern.tag_of("up_[..].pdf"), None);
+
+        assert!(TagPattern::parse(r"\[\w+\]").is_err());
+        assert!(TagPattern::parse(r"\[(\w+").is_err());
+    }
+}
@@ -0,0 +1,633 @@
+use std::fs::{self, File, FileTimes};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use crate::categories::compound_extension_len;
+
+/// What to do when a file of the same name already exists at the destination.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep both, giving the new file a numbered name (`name_1.ext`).
+    #[default]
+    Rename,
+    /// Leave the existing file and skip the new one.
+    Skip,
+    /// Replace the existing file, unless it already has the same contents.
+    Overwrite,
+    /// Replace the existing file only if the new one was modified later;
+    /// otherwise keep it and skip the new one.
+    Newest,
+}
+
+impl ConflictPolicy {
+    /// Whether files already at the destination can be replaced.
+    pub fn replaces(&self) -> bool {
+        matches!(self, ConflictPolicy::Overwrite | ConflictPolicy::Newest)
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rename" => Ok(ConflictPolicy::Rename),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "newest" => Ok(ConflictPolicy::Newest),
+            _ => Err(format!("unknown conflict mode '{}' (expected rename, skip, overwrite or newest)", s)),
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictPolicy::Rename => write!(f, "rename"),
+            ConflictPolicy::Skip => write!(f, "skip"),
+            ConflictPolicy::Overwrite => write!(f, "overwrite"),
+            ConflictPolicy::Newest => write!(f, "newest"),
+        }
+    }
+}
+
+/// What `--link` puts into the category folders instead of copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// A second name for the same file; only possible on the same file system.
+    Hard,
+    /// A symbolic link to where the file is.
+    Symbolic,
+}
+
+impl FromStr for LinkKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hard" => Ok(LinkKind::Hard),
+            "sym" => Ok(LinkKind::Symbolic),
+            _ => Err(format!("unknown link kind '{}' (expected hard or sym)", s)),
+        }
+    }
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkKind::Hard => write!(f, "hard"),
+            LinkKind::Symbolic => write!(f, "sym"),
+        }
+    }
+}
+
+/// Template for the name given to a file whose name is already taken, e.g.
+/// `{stem}_{n}{ext}` (the default) or `{stem} ({n}){ext}`.
+///
+/// Placeholders: `{stem}` (name without extension), `{ext}` (extension with
+/// its dot, or empty), `{n}` (counter starting at 1, required) and `{time}`
+/// (current local time as `YYYYMMDD-HHMMSS`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictFormat {
+    template: String,
+}
+
+impl ConflictFormat {
+    const PLACEHOLDERS: [&'static str; 4] = ["stem", "ext", "n", "time"];
+
+    /// Validates a template: only known placeholders, `{n}` present so every
+    /// attempt gives a new name, and no path separators.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let invalid = |why: &str| Err(format!("invalid conflict format '{}': {}", template, why));
+        if template.contains(['/', '\\']) {
+            return invalid("it must not contain path separators");
+        }
+        let mut rest = template;
+        let mut has_counter = false;
+        while let Some(open) = rest.find(['{', '}']) {
+            if rest[open..].starts_with('}') {
+                return invalid("unmatched '}'");
+            }
+            let Some(close) = rest[open..].find('}') else {
+                return invalid("unmatched '{'");
+            };
+            let name = &rest[open + 1..open + close];
+            if !Self::PLACEHOLDERS.contains(&name) {
+                let why = format!("unknown placeholder '{{{}}}' (expected {{stem}}, {{ext}}, {{n}} or {{time}})", name);
+                return invalid(&why);
+            }
+            has_counter |= name == "n";
+            rest = &rest[open + close + 1..];
+        }
+        if !has_counter {
+            return invalid("it needs an {n} counter");
+        }
+        Ok(ConflictFormat {
+            template: template.to_string(),
+        })
+    }
+
+    /// The template as given.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Fills in the template for attempt `n`.
+    pub fn render(&self, stem: &str, n: usize, ext: &str) -> String {
+        let mut name = self.template.replace("{stem}", stem).replace("{ext}", ext).replace("{n}", &n.to_string());
+        if name.contains("{time}") {
+            name = name.replace("{time}", &chrono::Local::now().format("%Y%m%d-%H%M%S").to_string());
+        }
+        name
+    }
+}
+
+impl Default for ConflictFormat {
+    fn default() -> Self {
+        ConflictFormat {
+            template: "{stem}_{n}{ext}".to_string(),
+        }
+    }
+}
+
+/// Picks a destination path inside `dest_dir` for `src`, appending a counter
+/// (`name_1.ext`, `name_2.ext`, ...) if the plain name is already taken.
+pub fn unique_destination(src: &Path, dest_dir: &Path) -> PathBuf {
+    unique_destination_with(src, dest_dir, |p| p.exists())
+}
+
+/// Like [`unique_destination`], but `is_taken` decides whether a candidate
+/// path is in use, so callers can also account for names they've reserved.
+pub fn unique_destination_with(src: &Path, dest_dir: &Path, is_taken: impl FnMut(&Path) -> bool) -> PathBuf {
+    unique_destination_with_format(src, dest_dir, &ConflictFormat::default(), is_taken)
+}
+
+/// Like [`unique_destination_with`], naming taken files after `format`.
+pub fn unique_destination_with_format(
+    src: &Path,
+    dest_dir: &Path,
+    format: &ConflictFormat,
+    mut is_taken: impl FnMut(&Path) -> bool,
+) -> PathBuf {
+    candidates(src, dest_dir, format).find(|p| !is_taken(p)).expect("candidate names never run out")
+}
+
+/// Destination paths to try for `src`, in order: the plain name, then the
+/// numbered names from `format`.
+fn candidates<'a>(src: &'a Path, dest_dir: &'a Path, format: &'a ConflictFormat) -> impl Iterator<Item = PathBuf> + 'a {
+    let file_name = src.file_name().expect("file should have a name");
+    let name = file_name.to_str().unwrap_or("file");
+    // Keep compound extensions together: backup_1.tar.gz, not backup.tar_1.gz
+    let (stem, ext) = match compound_extension_len(name) {
+        Some(len) => {
+            let (stem, ext) = name.split_at(name.len() - len - 1);
+            (stem, ext.to_string())
+        }
+        None => (
+            src.file_stem().and_then(|s| s.to_str()).unwrap_or("file"),
+            src.extension().and_then(|e| e.to_str()).map(|s| format!(".{}", s)).unwrap_or_default(),
+        ),
+    };
+    let numbered = (1..).map(move |count| dest_dir.join(format.render(stem, count, &ext)));
+    std::iter::once(dest_dir.join(file_name)).chain(numbered)
+}
+
+/// Device names Windows reserves, whatever extension follows (`NUL.txt` too).
+const WINDOWS_RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// `name` made usable as a file name on Windows: the trailing dots and spaces
+/// Windows would drop are removed, and a reserved device name gets a `_` after
+/// its stem (`CON.txt` -> `CON_.txt`). Other names are returned as they are.
+pub fn windows_safe_name(name: &str) -> String {
+    let name = match name.trim_end_matches(['.', ' ']) {
+        "" => name,
+        trimmed => trimmed,
+    };
+    // Windows only looks at the part before the first dot: `con.tar.gz` is reserved too
+    let (stem, rest) = name.split_at(name.find('.').unwrap_or(name.len()));
+    if WINDOWS_RESERVED.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem.trim_end())) {
+        format!("{}_{}", stem, rest)
+    } else {
+        name.to_string()
+    }
+}
+
+/// `error` from placing a file at `dest`, explained if the path is one the
+/// file system won't take. Long paths on Windows are not such a case: the
+/// standard library adds the `\\?\` prefix for them, so they get past `MAX_PATH`.
+pub fn explain_path_error(error: io::Error, dest: &Path) -> io::Error {
+    if error.kind() != io::ErrorKind::InvalidFilename {
+        return error;
+    }
+    let name_len = dest.file_name().map_or(0, |n| n.to_string_lossy().chars().count());
+    let path_len = dest.to_string_lossy().chars().count();
+    io::Error::new(
+        error.kind(),
+        format!(
+            "cannot create '{}': the name ({} characters) or the path ({} characters) is too long or not allowed here \
+             ({})",
+            dest.display(),
+            name_len,
+            path_len,
+            error
+        ),
+    )
+}
+
+/// Copies `src` to `dest`, which must not exist yet. The name is claimed by
+/// creating the file exclusively, so a file that appeared there in the
+/// meantime is never replaced; that fails with [`io::ErrorKind::AlreadyExists`].
+pub fn copy_new(src: &Path, dest: &Path) -> io::Result<()> {
+    let mut source = File::open(src)?;
+    let permissions = source.metadata()?.permissions();
+    let mut file = File::options().write(true).create_new(true).open(dest)?;
+    let copied = io::copy(&mut source, &mut file).and_then(|_| file.set_permissions(permissions));
+    if copied.is_err() {
+        // Don't leave a half-written file holding the name
+        let _ = fs::remove_file(dest);
+    }
+    copied
+}
+
+/// Links `dest` to `src`; `dest` must not exist yet, or this fails with
+/// [`io::ErrorKind::AlreadyExists`]. Symbolic links hold the absolute path
+/// of `src`, so they work wherever the category folder is.
+pub fn link_new(src: &Path, dest: &Path, kind: LinkKind) -> io::Result<()> {
+    match kind {
+        LinkKind::Hard => fs::hard_link(src, dest).map_err(|e| {
+            if e.kind() == io::ErrorKind::CrossesDevices {
+                io::Error::new(e.kind(), "hard links can't reach another file system (symbolic links can)")
+            } else {
+                e
+            }
+        }),
+        LinkKind::Symbolic => symlink(&std::path::absolute(src)?, dest),
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "symbolic links aren't supported on this platform"))
+}
+
+/// Copies `src` into `dest_dir` under the first free name from `format`,
+/// skipping names `is_taken` reports as in use. A name that turns out to be
+/// taken on disk when the copy is made is passed over for the next one.
+pub fn copy_to_unique_destination(
+    src: &Path,
+    dest_dir: &Path,
+    format: &ConflictFormat,
+    mut is_taken: impl FnMut(&Path) -> bool,
+) -> io::Result<PathBuf> {
+    for dest in candidates(src, dest_dir, format).filter(|p| !is_taken(p)) {
+        match copy_new(src, &dest) {
+            Ok(()) => return Ok(dest),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("candidate names never run out")
+}
+
+/// Copies `src` into `dest_dir` under a collision-free name, without ever
+/// replacing a file, even one created by another program while copying. The
+/// copy gets fresh timestamps; see [`copy_times`] to carry the originals over.
+pub fn copy_file_to_category(src: &Path, dest_dir: &Path) -> io::Result<PathBuf> {
+    copy_file_to_category_with_retries(src, dest_dir, 0)
+}
+
+/// Like [`copy_file_to_category`], but tries a copy that fails with a
+/// transient error (see [`with_retries`]) up to `retries` more times.
+pub fn copy_file_to_category_with_retries(src: &Path, dest_dir: &Path, retries: u32) -> io::Result<PathBuf> {
+    // Ensure destination directory exists
+    create_dir_all(dest_dir)?;
+    with_retries(retries, || copy_to_unique_destination(src, dest_dir, &ConflictFormat::default(), |_| false))
+}
+
+/// Runs `op`, and runs it again up to `retries` more times while it fails
+/// with an error that may go away by itself ([`io::ErrorKind::Interrupted`],
+/// [`io::ErrorKind::TimedOut`]), as network drives sometimes give. Waits
+/// 100 ms before the first retry and twice as long before each next one, up
+/// to 5 s. Other errors, like a missing file or denied permission, are
+/// returned right away.
+pub fn with_retries<T>(retries: u32, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delay = Duration::from_millis(100);
+    for _ in 0..retries {
+        match op() {
+            Err(e) if matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::TimedOut) => {
+                thread::sleep(delay);
+                delay = (delay * 2).min(Duration::from_secs(5));
+            }
+            result => return result,
+        }
+    }
+    op()
+}
+
+/// Moves `src` into `dest_dir` under a collision-free name. A name that
+/// turns out to be taken when the move is made is passed over for the next one.
+pub fn move_file_to_category(src: &Path, dest_dir: &Path) -> io::Result<PathBuf> {
+    create_dir_all(dest_dir)?;
+    loop {
+        let dest_path = unique_destination(src, dest_dir);
+        match move_new(src, &dest_path) {
+            Ok(()) => return Ok(dest_path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// `fs::create_dir_all`, saying which file is in the way when one of the
+/// folders can't be created because a file has its name.
+pub(crate) fn create_dir_all(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir).map_err(|e| match dir.ancestors().find(|d| d.is_file()) {
+        Some(file) => io::Error::new(
+            e.kind(),
+            format!("'{}' is a file, so the folder '{}' can't be created", file.display(), dir.display()),
+        ),
+        None => e,
+    })
+}
+
+/// Moves `src` to exactly `dest`. Tries a cheap `rename` first and falls back
+/// to copy-then-delete when the destination is on another filesystem.
+pub fn move_file(src: &Path, dest: &Path) -> io::Result<()> {
+    match fs::rename(src, dest) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(src, dest)?;
+            // A rename keeps the timestamps, so the fallback should too
+            let _ = copy_times(src, dest);
+            fs::remove_file(src)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Like [`move_file`], but never replaces a file at `dest`: if one is there,
+/// or appears while the move is made, this fails with
+/// [`io::ErrorKind::AlreadyExists`] and `src` stays where it is.
+pub fn move_new(src: &Path, dest: &Path) -> io::Result<()> {
+    match rename_new(src, dest) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => copy_then_remove(src, dest),
+        Err(e) => Err(e),
+    }
+}
+
+/// `rename` that refuses to replace `dest`.
+#[cfg(target_os = "linux")]
+fn rename_new(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = |path: &Path| CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other);
+    let (c_src, c_dest) = (c_path(src)?, c_path(dest)?);
+    // SAFETY: both are valid C strings, relative paths resolve against the working directory
+    let status = unsafe {
+        libc::renameat2(libc::AT_FDCWD, c_src.as_ptr(), libc::AT_FDCWD, c_dest.as_ptr(), libc::RENAME_NOREPLACE)
+    };
+    if status == 0 {
+        return Ok(());
+    }
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        // Kernels and file systems that can't refuse to replace
+        Some(libc::EINVAL | libc::ENOSYS) => link_then_remove(src, dest),
+        _ => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rename_new(src: &Path, dest: &Path) -> io::Result<()> {
+    link_then_remove(src, dest)
+}
+
+/// Renames by giving the file its new name as a hard link, which fails if
+/// the name is taken, and then dropping the old one.
+fn link_then_remove(src: &Path, dest: &Path) -> io::Result<()> {
+    match fs::hard_link(src, dest) {
+        Ok(()) => fs::remove_file(src).inspect_err(|_| {
+            let _ = fs::remove_file(dest);
+        }),
+        Err(e) if matches!(e.kind(), io::ErrorKind::AlreadyExists | io::ErrorKind::NotFound) => Err(e),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => Err(e),
+        // No hard links there (FAT, some network drives); a copy claims the name just as safely
+        Err(_) => copy_then_remove(src, dest),
+    }
+}
+
+/// Moves across file systems: copies into a newly created `dest`, keeping
+/// the timestamps as a rename does, and removes `src`.
+fn copy_then_remove(src: &Path, dest: &Path) -> io::Result<()> {
+    copy_new(src, dest)?;
+    let _ = copy_times(src, dest);
+    fs::remove_file(src)
+}
+
+/// Sets the accessed and modified times of `dest` to those of `src`. Fails with
+/// [`io::ErrorKind::Unsupported`] where the platform can't set file times.
+pub fn copy_times(src: &Path, dest: &Path) -> io::Result<()> {
+    let meta = fs::metadata(src)?;
+    let times = FileTimes::new().set_accessed(meta.accessed()?).set_modified(meta.modified()?);
+    File::options().write(true).open(dest)?.set_times(times)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_never_replace_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        fs::write(&src, b"new").unwrap();
+        fs::write(&dest, b"old").unwrap();
+
+        assert_eq!(move_new(&src, &dest).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&dest).unwrap(), b"old");
+        assert_eq!(fs::read(&src).unwrap(), b"new");
+        // The same without rename support, as on file systems that lack it
+        assert_eq!(link_then_remove(&src, &dest).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(copy_then_remove(&src, &dest).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert!(src.exists());
+
+        let free = dir.path().join("c.txt");
+        move_new(&src, &free).unwrap();
+        assert_eq!(fs::read(&free).unwrap(), b"new");
+        assert!(!src.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_point_at_the_original_and_never_replace_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("song.mp3");
+        fs::write(&src, b"tune").unwrap();
+
+        let (hard, sym) = (dir.path().join("hard.mp3"), dir.path().join("sym.mp3"));
+        link_new(&src, &hard, LinkKind::Hard).unwrap();
+        link_new(&src, &sym, LinkKind::Symbolic).unwrap();
+        fs::write(&src, b"new tune").unwrap();
+        assert_eq!(fs::read(&hard).unwrap(), b"new tune");
+        assert_eq!(fs::read_link(&sym).unwrap(), src);
+
+        let err = link_new(&src, &hard, LinkKind::Symbolic).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn copy_creates_the_category_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("photo.jpg");
+        fs::write(&src, b"pixels").unwrap();
+
+        let dest = copy_file_to_category(&src, &dir.path().join("Images")).unwrap();
+        assert_eq!(dest, dir.path().join("Images").join("photo.jpg"));
+        assert_eq!(fs::read(&dest).unwrap(), b"pixels");
+        assert!(src.exists());
+    }
+
+    #[test]
+    fn copy_appends_a_counter_on_collision() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("photo.jpg");
+        fs::write(&src, b"new").unwrap();
+        let images = dir.path().join("Images");
+        fs::create_dir(&images).unwrap();
+        fs::write(images.join("photo.jpg"), b"old").unwrap();
+        fs::write(images.join("photo_1.jpg"), b"older").unwrap();
+
+        let dest = copy_file_to_category(&src, &images).unwrap();
+        assert_eq!(dest, images.join("photo_2.jpg"));
+        assert_eq!(fs::read(images.join("photo.jpg")).unwrap(), b"old");
+        assert_eq!(fs::read(&dest).unwrap(), b"new");
+    }
+
+    #[test]
+    fn copy_passes_over_names_taken_since_they_were_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("report.pdf");
+        fs::write(&src, b"new").unwrap();
+        let docs = dir.path().join("Documents");
+        fs::create_dir(&docs).unwrap();
+        // As if another program created these right after the check said they were free
+        for name in ["report.pdf", "report_1.pdf", "report_2.pdf"] {
+            fs::write(docs.join(name), name).unwrap();
+        }
+
+        let dest = copy_to_unique_destination(&src, &docs, &ConflictFormat::default(), |_| false).unwrap();
+        assert_eq!(dest, docs.join("report_3.pdf"));
+        assert_eq!(fs::read(&dest).unwrap(), b"new");
+        for name in ["report.pdf", "report_1.pdf", "report_2.pdf"] {
+            assert_eq!(fs::read(docs.join(name)).unwrap(), name.as_bytes());
+        }
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let mut calls = 0;
+        let result: io::Result<()> = with_retries(2, || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::TimedOut))
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(calls, 3);
+
+        calls = 0;
+        let result = with_retries(2, || {
+            calls += 1;
+            match calls {
+                1 => Err(io::Error::from(io::ErrorKind::Interrupted)),
+                _ => Ok(calls),
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+
+        calls = 0;
+        let result: io::Result<()> = with_retries(2, || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn copy_new_never_replaces_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        fs::write(&src, b"a").unwrap();
+        fs::write(&dest, b"b").unwrap();
+
+        let err = copy_new(&src, &dest).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&dest).unwrap(), b"b");
+    }
+
+    #[test]
+    fn counter_goes_before_the_extension() {
+        let dest = unique_destination_with(Path::new("notes"), Path::new("/out"), |p| p == Path::new("/out/notes"));
+        assert_eq!(dest, Path::new("/out/notes_1"));
+    }
+
+    #[test]
+    fn counter_keeps_compound_extensions_together() {
+        let taken = Path::new("/out/backup.tar.gz");
+        let dest = unique_destination_with(Path::new("backup.tar.gz"), Path::new("/out"), |p| p == taken);
+        assert_eq!(dest, Path::new("/out/backup_1.tar.gz"));
+    }
+
+    #[test]
+    fn conflict_format_is_validated_and_applied() {
+        let browser = ConflictFormat::parse("{stem} ({n}){ext}").unwrap();
+        let dest = unique_destination_with_format(Path::new("a.txt"), Path::new("/out"), &browser, |p| {
+            p == Path::new("/out/a.txt") || p == Path::new("/out/a (1).txt")
+        });
+        assert_eq!(dest, Path::new("/out/a (2).txt"));
+
+        assert!(ConflictFormat::parse("{stem}{ext}").is_err());
+        assert!(ConflictFormat::parse("{stem}-{count}{ext}").is_err());
+        assert!(ConflictFormat::parse("{stem}/{n}{ext}").is_err());
+        assert!(ConflictFormat::parse("{stem}-{n{ext}").is_err());
+    }
+
+    #[test]
+    fn reserved_windows_names_get_an_underscore() {
+        assert_eq!(windows_safe_name("CON.txt"), "CON_.txt");
+        assert_eq!(windows_safe_name("nul"), "nul_");
+        assert_eq!(windows_safe_name("com1.tar.gz"), "com1_.tar.gz");
+        assert_eq!(windows_safe_name("notes. "), "notes");
+        assert_eq!(windows_safe_name("CONSOLE.txt"), "CONSOLE.txt");
+        assert_eq!(windows_safe_name("my con.txt"), "my con.txt");
+    }
+
+    #[test]
+    fn move_removes_the_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("song.mp3");
+        fs::write(&src, b"la").unwrap();
+
+        let dest = move_file_to_category(&src, &dir.path().join("Audio")).unwrap();
+        assert!(!src.exists());
+        assert_eq!(fs::read(dest).unwrap(), b"la");
+    }
+}
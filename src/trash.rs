@@ -0,0 +1,139 @@
+//! `--trash-on-overwrite`: a file that `--on-conflict overwrite` is about to
+//! replace is moved to the trash first, so it can still be brought back.
+//!
+//! On Linux and other Unix desktops this is the freedesktop.org home trash
+//! (`~/.local/share/Trash`), on macOS `~/.Trash`. Where there is no trash, or
+//! the file can't be moved into it (e.g. it lives on another drive), it goes
+//! to a [`FALLBACK_TRASH_DIR`] folder in the output folder instead.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::transfer::unique_destination;
+
+/// Folder inside the output folder that replaced files go to when the
+/// system trash can't be used.
+pub const FALLBACK_TRASH_DIR: &str = ".organizer-trash";
+
+/// Where a trashed file went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trashed {
+    /// Into the system trash, at this path.
+    System(PathBuf),
+    /// Into [`FALLBACK_TRASH_DIR`], at this path; the string says why the
+    /// system trash wasn't used.
+    Fallback(PathBuf, String),
+}
+
+/// Moves `path` to the system trash, or to [`FALLBACK_TRASH_DIR`] in
+/// `out_root` if that doesn't work. Fails only if neither does, in which
+/// case `path` is left where it was.
+pub fn trash_file(path: &Path, out_root: &Path) -> io::Result<Trashed> {
+    let reason = match system_trash(path) {
+        Ok(trashed) => return Ok(Trashed::System(trashed)),
+        Err(e) => e.to_string(),
+    };
+    let dir = out_root.join(FALLBACK_TRASH_DIR);
+    fs::create_dir_all(&dir)?;
+    let dest = unique_destination(path, &dir);
+    fs::rename(path, &dest)?;
+    Ok(Trashed::Fallback(dest, reason))
+}
+
+/// The freedesktop.org home trash: the file goes to `files/`, with a
+/// `.trashinfo` in `info/` recording where it came from so file managers
+/// can restore it.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn system_trash(path: &Path) -> io::Result<PathBuf> {
+    use std::io::Write;
+
+    let data_home = match std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()?.join(".local/share"),
+    };
+    let trash = data_home.join("Trash");
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let original = std::path::absolute(path)?;
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+    for n in 1.. {
+        let trashed_name = if n == 1 { name.to_string() } else { format!("{}.{}", name, n) };
+        let info_path = info.join(format!("{}.trashinfo", trashed_name));
+        let dest = files.join(&trashed_name);
+        if dest.exists() {
+            continue;
+        }
+        // Creating the info file claims the name
+        let mut info_file = match fs::File::options().write(true).create_new(true).open(&info_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+        let deleted = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S");
+        let written = write!(
+            info_file,
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode(&original),
+            deleted
+        );
+        if let Err(e) = written.and_then(|()| fs::rename(path, &dest)) {
+            let _ = fs::remove_file(&info_path);
+            return Err(e);
+        }
+        return Ok(dest);
+    }
+    unreachable!("ran out of trash names")
+}
+
+#[cfg(target_os = "macos")]
+fn system_trash(path: &Path) -> io::Result<PathBuf> {
+    let trash = home_dir()?.join(".Trash");
+    fs::create_dir_all(&trash)?;
+    let dest = unique_destination(path, &trash);
+    fs::rename(path, &dest)?;
+    Ok(dest)
+}
+
+#[cfg(not(unix))]
+fn system_trash(_path: &Path) -> io::Result<PathBuf> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the system trash isn't supported on this platform"))
+}
+
+#[cfg(unix)]
+fn home_dir() -> io::Result<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))
+}
+
+/// `Path=` value of a `.trashinfo` file: the path with everything but
+/// unreserved characters and `/` percent-encoded.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn percent_encode(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn trashinfo_paths_are_percent_encoded() {
+        assert_eq!(percent_encode(Path::new("/home/me/My File #1.txt")), "/home/me/My%20File%20%231.txt");
+    }
+}
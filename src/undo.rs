@@ -0,0 +1,116 @@
+//! `--undo`: reverts the operations recorded in an [`oplog`](crate::oplog) file.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::hash::file_sha256;
+use crate::oplog::{read_log, LogEntry};
+use crate::transfer::move_file;
+
+/// Slack allowed between a destination's mtime and the logged timestamp, for
+/// filesystems with coarse timestamps.
+const MTIME_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// Result of reverting one log entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UndoStatus {
+    /// A copy (or link) whose destination was deleted.
+    Deleted,
+    /// A move whose file was put back at its original location.
+    Restored,
+    /// Would be deleted/restored (dry run).
+    WouldUndo,
+    /// Left alone for safety; the string says why.
+    Skipped(String),
+}
+
+/// One reverted (or skipped) log entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoAction {
+    pub entry: LogEntry,
+    pub status: UndoStatus,
+}
+
+/// Everything an undo run did, newest log entry first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UndoSummary {
+    pub actions: Vec<UndoAction>,
+}
+
+impl UndoSummary {
+    /// Number of entries that were (or would be) reverted.
+    pub fn undone(&self) -> usize {
+        self.actions.iter().filter(|a| !matches!(a.status, UndoStatus::Skipped(_))).count()
+    }
+
+    /// Number of entries left alone.
+    pub fn skipped(&self) -> usize {
+        self.actions.len() - self.undone()
+    }
+}
+
+/// Reverts every entry in the log at `log_path`, newest first. Copies are
+/// undone by deleting the destination, but only while its contents still
+/// match the source; moves are undone by moving the file back. Destinations
+/// modified after the logged operation are never touched.
+pub fn undo(log_path: &Path, dry_run: bool) -> io::Result<UndoSummary> {
+    let entries = read_log(log_path)?;
+    let mut summary = UndoSummary::default();
+    for entry in entries.into_iter().rev() {
+        let status = match undo_entry(&entry, dry_run) {
+            Ok(status) => status,
+            Err(e) => UndoStatus::Skipped(e.to_string()),
+        };
+        summary.actions.push(UndoAction { entry, status });
+    }
+    Ok(summary)
+}
+
+fn undo_entry(entry: &LogEntry, dry_run: bool) -> io::Result<UndoStatus> {
+    let skip = |reason: &str| Ok(UndoStatus::Skipped(reason.to_string()));
+
+    let dest_meta = match fs::metadata(&entry.destination) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return skip("destination no longer exists"),
+        Err(e) => return Err(e),
+    };
+    let Some(logged_at) = entry.time() else {
+        return skip("log entry has an invalid timestamp");
+    };
+    let logged_at = SystemTime::from(logged_at);
+    if dest_meta.modified()? > logged_at + MTIME_TOLERANCE {
+        return skip("destination was modified after it was organized");
+    }
+
+    match entry.action.as_str() {
+        "copy" | "link" => {
+            if !entry.source.exists() {
+                return skip("original is gone, so the copy can't be verified");
+            }
+            if file_sha256(&entry.source)? != file_sha256(&entry.destination)? {
+                return skip("copy no longer matches the original");
+            }
+            if dry_run {
+                return Ok(UndoStatus::WouldUndo);
+            }
+            fs::remove_file(&entry.destination)?;
+            Ok(UndoStatus::Deleted)
+        }
+        "move" => {
+            if entry.source.exists() {
+                return skip("something already exists at the original location");
+            }
+            if dry_run {
+                return Ok(UndoStatus::WouldUndo);
+            }
+            if let Some(parent) = entry.source.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            move_file(&entry.destination, &entry.source)?;
+            Ok(UndoStatus::Restored)
+        }
+        other => skip(&format!("unknown action '{}'", other)),
+    }
+}
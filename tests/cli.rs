@@ -0,0 +1,42 @@
+//! Runs the binary itself, for what only the command line decides.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn file_organizer(folder: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_file_organizer")).arg(folder).args(args).output().unwrap()
+}
+
+#[test]
+fn a_rejected_command_line_leaves_the_csv_file_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("cat.jpg"), b"meow").unwrap();
+    let csv = dir.path().join("keep.csv");
+    fs::write(&csv, "kept\n").unwrap();
+    let csv_arg = csv.to_str().unwrap();
+    for args in [["--version"], ["--stats-only"], ["--no-such-flag"]] {
+        file_organizer(dir.path(), &["--dry-run", "--csv", csv_arg, args[0]]);
+        assert_eq!(fs::read_to_string(&csv).unwrap(), "kept\n", "after {}", args[0]);
+    }
+
+    let output = file_organizer(dir.path(), &["--dry-run", "--csv", csv_arg]);
+    assert!(output.status.success());
+    assert!(fs::read_to_string(&csv).unwrap().starts_with("source,destination,category,size,status\n"));
+}
+
+#[test]
+fn a_csv_only_run_still_has_a_row_and_a_count_for_every_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let folder = dir.path().join("in");
+    fs::create_dir(&folder).unwrap();
+    for name in ["a.jpg", "b.jpg", "notes.txt"] {
+        fs::write(folder.join(name), b"x").unwrap();
+    }
+    let csv = dir.path().join("report.csv");
+    let output = file_organizer(&folder, &["--dry-run", "--quiet", "--csv", csv.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&csv).unwrap().lines().filter(|row| row.ends_with(",would_copy")).count(), 3);
+    let summary = String::from_utf8(output.stdout).unwrap();
+    assert!(summary.contains("Total would copy: 3"), "{}", summary);
+}